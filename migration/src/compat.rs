@@ -0,0 +1,73 @@
+//! The parts of the schema that have to be written differently for MySQL, Postgres and SQLite
+
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, DbBackend, Statement},
+};
+
+/// sea-query can't make an unsigned column auto increment on Postgres, which has no
+/// unsigned types. Ids start out as `serial` there and become `oid` in a later migration.
+pub trait ColumnDefExt {
+    /// An unsigned int to auto increment, or a plain int on Postgres
+    fn unsigned_id(&mut self, backend: DbBackend) -> &mut Self;
+}
+
+impl ColumnDefExt for ColumnDef {
+    fn unsigned_id(&mut self, backend: DbBackend) -> &mut Self {
+        match backend {
+            DbBackend::Postgres => self.integer(),
+            _ => self.unsigned(),
+        }
+    }
+}
+
+/// Runs raw SQL, for what sea-query has no statement for
+pub async fn execute(manager: &SchemaManager<'_>, sql: String) -> Result<(), DbErr> {
+    let backend = manager.get_database_backend();
+    manager
+        .get_connection()
+        .execute(Statement::from_string(backend, sql))
+        .await
+        .map(|_| ())
+}
+
+/// sea-query refuses to drop columns on SQLite, although SQLite has supported it since 3.35
+pub async fn drop_column<T, C>(
+    manager: &SchemaManager<'_>,
+    table: T,
+    column: C,
+) -> Result<(), DbErr>
+where
+    T: Iden + 'static,
+    C: Iden + 'static,
+{
+    if manager.get_database_backend() == DbBackend::Sqlite {
+        return execute(
+            manager,
+            format!(
+                r#"ALTER TABLE "{}" DROP COLUMN "{}""#,
+                table.to_string(),
+                column.to_string()
+            ),
+        )
+        .await;
+    }
+
+    manager
+        .alter_table(Table::alter().table(table).drop_column(column).to_owned())
+        .await
+}
+
+/// sea-query writes `DROP INDEX ... ON table` for SQLite, which SQLite doesn't accept
+pub async fn drop_index<T>(manager: &SchemaManager<'_>, table: T, name: &str) -> Result<(), DbErr>
+where
+    T: Iden + 'static,
+{
+    if manager.get_database_backend() == DbBackend::Sqlite {
+        return execute(manager, format!(r#"DROP INDEX "{}""#, name)).await;
+    }
+
+    manager
+        .drop_index(Index::drop().name(name).table(table).to_owned())
+        .await
+}
@@ -1,7 +1,18 @@
 pub use sea_orm_migration::prelude::*;
 
+mod compat;
 mod m20220101_000001_create_table;
 mod m20220723_133540_add_blog_description;
+mod m20220805_190412_add_comment_status;
+mod m20220812_201530_create_tags;
+mod m20220820_143307_add_post_slug;
+mod m20220827_102245_add_post_search_index;
+mod m20220903_171858_add_post_status;
+mod m20220910_094512_create_post_revisions;
+mod m20220917_153021_create_media;
+mod m20220924_110342_add_post_preview_size;
+mod m20221001_091204_create_old_slugs;
+mod m20221002_154410_postgres_oid_columns;
 
 pub struct Migrator;
 
@@ -11,6 +22,16 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20220101_000001_create_table::Migration),
             Box::new(m20220723_133540_add_blog_description::Migration),
+            Box::new(m20220805_190412_add_comment_status::Migration),
+            Box::new(m20220812_201530_create_tags::Migration),
+            Box::new(m20220820_143307_add_post_slug::Migration),
+            Box::new(m20220827_102245_add_post_search_index::Migration),
+            Box::new(m20220903_171858_add_post_status::Migration),
+            Box::new(m20220910_094512_create_post_revisions::Migration),
+            Box::new(m20220917_153021_create_media::Migration),
+            Box::new(m20220924_110342_add_post_preview_size::Migration),
+            Box::new(m20221001_091204_create_old_slugs::Migration),
+            Box::new(m20221002_154410_postgres_oid_columns::Migration),
         ]
     }
 }
@@ -1,3 +1,4 @@
+use crate::compat::ColumnDefExt;
 use sea_orm_migration::prelude::*;
 
 #[derive(DeriveMigrationName)]
@@ -6,6 +7,8 @@ pub struct Migration;
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let backend = manager.get_database_backend();
+
         manager
             .create_table(
                 Table::create()
@@ -13,7 +16,7 @@ impl MigrationTrait for Migration {
                     .if_not_exists()
                     .col(
                         ColumnDef::new(Users::UserId)
-                            .unsigned()
+                            .unsigned_id(backend)
                             .not_null()
                             .auto_increment()
                             .primary_key(),
@@ -38,7 +41,7 @@ impl MigrationTrait for Migration {
                     .if_not_exists()
                     .col(
                         ColumnDef::new(BlogPosts::PostId)
-                            .unsigned()
+                            .unsigned_id(backend)
                             .not_null()
                             .auto_increment()
                             .primary_key(),
@@ -66,7 +69,7 @@ impl MigrationTrait for Migration {
                     .if_not_exists()
                     .col(
                         ColumnDef::new(Comments::CommentId)
-                            .unsigned()
+                            .unsigned_id(backend)
                             .not_null()
                             .auto_increment()
                             .primary_key(),
@@ -1,4 +1,5 @@
-use sea_orm_migration::prelude::*;
+use crate::compat::drop_column;
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
 
 #[derive(DeriveMigrationName)]
 pub struct Migration;
@@ -6,6 +7,24 @@ pub struct Migration;
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can't alter more than one column per statement
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            for column in [
+                ColumnDef::new(BlogPosts::Description).string_len(200),
+                ColumnDef::new(BlogPosts::LastUpdated).date_time(),
+            ] {
+                manager
+                    .alter_table(
+                        Table::alter()
+                            .table(BlogPosts::Table)
+                            .add_column(column)
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+            return Ok(());
+        }
+
         manager
             .alter_table(
                 Table::alter()
@@ -18,6 +37,11 @@ impl MigrationTrait for Migration {
     }
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            drop_column(manager, BlogPosts::Table, BlogPosts::Description).await?;
+            return drop_column(manager, BlogPosts::Table, BlogPosts::LastUpdated).await;
+        }
+
         manager
             .alter_table(
                 Table::alter()
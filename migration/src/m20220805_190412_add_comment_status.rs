@@ -0,0 +1,44 @@
+use crate::compat::drop_column;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Comments::Table)
+                    .add_column(
+                        ColumnDef::new(Comments::Status)
+                            .string_len(8)
+                            .not_null()
+                            .default("pending"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Comments that already exist were added by hand, so keep them visible
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Comments::Table)
+                    .value(Comments::Status, "approved".into())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_column(manager, Comments::Table, Comments::Status).await
+    }
+}
+
+#[derive(Iden)]
+enum Comments {
+    Table,
+    Status,
+}
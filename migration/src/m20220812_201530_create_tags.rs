@@ -0,0 +1,94 @@
+use crate::compat::ColumnDefExt;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let backend = manager.get_database_backend();
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Tags::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Tags::TagId)
+                            .unsigned_id(backend)
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Tags::Slug)
+                            .string_len(60)
+                            .unique_key()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Tags::Name).string_len(60).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PostTags::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(PostTags::PostId).unsigned().not_null())
+                    .col(ColumnDef::new(PostTags::TagId).unsigned().not_null())
+                    .primary_key(Index::create().col(PostTags::PostId).col(PostTags::TagId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_post_tag_post")
+                            .from(PostTags::Table, PostTags::PostId)
+                            .to(BlogPosts::Table, BlogPosts::PostId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_post_tag_tag")
+                            .from(PostTags::Table, PostTags::TagId)
+                            .to(Tags::Table, Tags::TagId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PostTags::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Tags::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Tags {
+    Table,
+    TagId,
+    Slug,
+    Name,
+}
+
+#[derive(Iden)]
+enum PostTags {
+    Table,
+    PostId,
+    TagId,
+}
+
+#[derive(Iden)]
+enum BlogPosts {
+    Table,
+    PostId,
+}
@@ -0,0 +1,116 @@
+use crate::compat::{drop_column, drop_index};
+use sea_orm_migration::{prelude::*, sea_orm::ConnectionTrait};
+use std::collections::HashSet;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Room for a 100 character title plus a suffix to keep it unique
+const SLUG_LEN: usize = 110;
+
+/// Copy of how the blog crate builds post slugs at the time of this migration
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    let words = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty());
+
+    for word in words {
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.extend(word.chars().flat_map(char::to_lowercase));
+    }
+
+    // Numeric slugs would be mistaken for the old /post/{id} urls
+    if slug.is_empty() {
+        slug.push_str("post");
+    } else if slug.chars().all(|c| c.is_ascii_digit()) {
+        slug.insert_str(0, "post-");
+    }
+
+    slug
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BlogPosts::Table)
+                    .add_column(
+                        ColumnDef::new(BlogPosts::Slug)
+                            .string_len(SLUG_LEN as u32)
+                            .not_null()
+                            .default(""),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Backfill slugs from titles, numbering duplicates in post order
+        let db = manager.get_connection();
+        let builder = manager.get_database_backend();
+        let posts = db
+            .query_all(
+                builder.build(
+                    Query::select()
+                        .columns([BlogPosts::PostId, BlogPosts::Title])
+                        .from(BlogPosts::Table)
+                        .order_by(BlogPosts::PostId, Order::Asc),
+                ),
+            )
+            .await?;
+
+        let mut used = HashSet::new();
+        for post in posts {
+            let post_id: u32 = post.try_get("", "post_id")?;
+            let title: String = post.try_get("", "title")?;
+
+            let base = slugify(&title);
+            let mut slug = base.clone();
+            let mut n = 1;
+            while used.contains(&slug) {
+                n += 1;
+                slug = format!("{}-{}", base, n);
+            }
+
+            manager
+                .exec_stmt(
+                    Query::update()
+                        .table(BlogPosts::Table)
+                        .value(BlogPosts::Slug, slug.clone().into())
+                        .and_where(Expr::col(BlogPosts::PostId).eq(post_id))
+                        .to_owned(),
+                )
+                .await?;
+            used.insert(slug);
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_post_slug")
+                    .table(BlogPosts::Table)
+                    .col(BlogPosts::Slug)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_index(manager, BlogPosts::Table, "idx_post_slug").await?;
+
+        drop_column(manager, BlogPosts::Table, BlogPosts::Slug).await
+    }
+}
+
+#[derive(Iden)]
+enum BlogPosts {
+    Table,
+    PostId,
+    Title,
+    Slug,
+}
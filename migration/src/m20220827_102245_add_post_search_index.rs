@@ -0,0 +1,51 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // This is MySQL's full-text index. Postgres gets its own in a later migration,
+        // and SQLite searches without one.
+        if manager.get_database_backend() != DbBackend::MySql {
+            return Ok(());
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_post_search")
+                    .table(BlogPosts::Table)
+                    .col(BlogPosts::Title)
+                    .col(BlogPosts::Description)
+                    .col(BlogPosts::Body)
+                    .full_text()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::MySql {
+            return Ok(());
+        }
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_post_search")
+                    .table(BlogPosts::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum BlogPosts {
+    Table,
+    Title,
+    Description,
+    Body,
+}
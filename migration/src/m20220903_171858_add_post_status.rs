@@ -0,0 +1,35 @@
+use crate::compat::drop_column;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Every post that already exists has been public
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BlogPosts::Table)
+                    .add_column(
+                        ColumnDef::new(BlogPosts::Status)
+                            .string_len(9)
+                            .not_null()
+                            .default("published"),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_column(manager, BlogPosts::Table, BlogPosts::Status).await
+    }
+}
+
+#[derive(Iden)]
+enum BlogPosts {
+    Table,
+    Status,
+}
@@ -0,0 +1,128 @@
+use crate::compat::ColumnDefExt;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let backend = manager.get_database_backend();
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PostRevisions::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PostRevisions::RevisionId)
+                            .unsigned_id(backend)
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PostRevisions::PostId).unsigned().not_null())
+                    .col(ColumnDef::new(PostRevisions::EditorId).unsigned())
+                    .col(
+                        ColumnDef::new(PostRevisions::Title)
+                            .string_len(100)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PostRevisions::Description).string_len(200))
+                    .col(ColumnDef::new(PostRevisions::Body).text().not_null())
+                    .col(
+                        ColumnDef::new(PostRevisions::SavedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_revision_post")
+                            .from(PostRevisions::Table, PostRevisions::PostId)
+                            .to(BlogPosts::Table, BlogPosts::PostId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_revision_editor")
+                            .from(PostRevisions::Table, PostRevisions::EditorId)
+                            .to(Users::Table, Users::UserId)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Start every existing post's history with its current content
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(PostRevisions::Table)
+                    .columns([
+                        PostRevisions::PostId,
+                        PostRevisions::EditorId,
+                        PostRevisions::Title,
+                        PostRevisions::Description,
+                        PostRevisions::Body,
+                        PostRevisions::SavedAt,
+                    ])
+                    .select_from(
+                        Query::select()
+                            .columns([
+                                BlogPosts::PostId,
+                                BlogPosts::AuthorId,
+                                BlogPosts::Title,
+                                BlogPosts::Description,
+                                BlogPosts::Body,
+                            ])
+                            .expr(Func::coalesce([
+                                Expr::col(BlogPosts::LastUpdated),
+                                Expr::col(BlogPosts::PostDate),
+                            ]))
+                            .from(BlogPosts::Table)
+                            .to_owned(),
+                    )
+                    .map_err(|e| DbErr::Migration(e.to_string()))?
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PostRevisions::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum PostRevisions {
+    Table,
+    RevisionId,
+    PostId,
+    EditorId,
+    Title,
+    Description,
+    Body,
+    SavedAt,
+}
+
+#[derive(Iden)]
+enum BlogPosts {
+    Table,
+    PostId,
+    AuthorId,
+    Title,
+    Description,
+    Body,
+    PostDate,
+    LastUpdated,
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    UserId,
+}
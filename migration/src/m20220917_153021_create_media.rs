@@ -0,0 +1,94 @@
+use crate::compat::ColumnDefExt;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let backend = manager.get_database_backend();
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Media::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Media::MediaId)
+                            .unsigned_id(backend)
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Media::UploaderId).unsigned())
+                    .col(
+                        ColumnDef::new(Media::FileName)
+                            .string_len(150)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Media::OriginalName)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Media::ContentType)
+                            .string_len(100)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Media::Size)
+                            .unsigned()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Media::Width).unsigned())
+                    .col(ColumnDef::new(Media::Height).unsigned())
+                    .col(ColumnDef::new(Media::Thumbnail).string_len(150))
+                    .col(
+                        ColumnDef::new(Media::UploadedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_media_uploader")
+                            .from(Media::Table, Media::UploaderId)
+                            .to(Users::Table, Users::UserId)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Media::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+#[allow(clippy::enum_variant_names)]
+enum Media {
+    Table,
+    MediaId,
+    UploaderId,
+    FileName,
+    OriginalName,
+    ContentType,
+    Size,
+    Width,
+    Height,
+    Thumbnail,
+    UploadedAt,
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    UserId,
+}
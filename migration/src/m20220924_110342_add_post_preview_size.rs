@@ -0,0 +1,30 @@
+use crate::compat::drop_column;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Posts without one use the `preview_size` from the config
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BlogPosts::Table)
+                    .add_column(ColumnDef::new(BlogPosts::PreviewSize).unsigned())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_column(manager, BlogPosts::Table, BlogPosts::PreviewSize).await
+    }
+}
+
+#[derive(Iden)]
+enum BlogPosts {
+    Table,
+    PreviewSize,
+}
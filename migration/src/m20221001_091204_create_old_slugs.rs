@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Same as the slug column of blog_posts
+const SLUG_LEN: u32 = 110;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OldSlugs::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OldSlugs::Slug)
+                            .string_len(SLUG_LEN)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(OldSlugs::PostId)
+                            .unsigned()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_old_slugs_post")
+                            .from(OldSlugs::Table, OldSlugs::PostId)
+                            .to(BlogPosts::Table, BlogPosts::PostId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OldSlugs::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum OldSlugs {
+    Table,
+    Slug,
+    PostId,
+}
+
+#[derive(Iden)]
+enum BlogPosts {
+    Table,
+    PostId,
+}
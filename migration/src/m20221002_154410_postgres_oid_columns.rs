@@ -0,0 +1,89 @@
+use crate::compat::execute;
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+/// Postgres has no unsigned ints, and `oid` is the only type sea-orm reads a `u32` from there,
+/// so the earlier migrations' integer columns are turned into `oid`. Postgres also gets the
+/// search index MySQL got in `m20220827_102245_add_post_search_index`.
+/// Other backends are left alone.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Every column the blog reads as an unsigned int
+const UNSIGNED_COLUMNS: &[(&str, &str)] = &[
+    ("users", "user_id"),
+    ("users", "level"),
+    ("blog_posts", "post_id"),
+    ("blog_posts", "author_id"),
+    ("blog_posts", "preview_size"),
+    ("comments", "comment_id"),
+    ("comments", "post_id"),
+    ("comments", "author_id"),
+    ("tags", "tag_id"),
+    ("post_tags", "post_id"),
+    ("post_tags", "tag_id"),
+    ("post_revisions", "revision_id"),
+    ("post_revisions", "post_id"),
+    ("post_revisions", "editor_id"),
+    ("media", "media_id"),
+    ("media", "uploader_id"),
+    ("media", "size"),
+    ("media", "width"),
+    ("media", "height"),
+    ("old_slugs", "post_id"),
+];
+
+/// The expression the blog searches Postgres with
+const SEARCH_DOCUMENT: &str =
+    r#"to_tsvector('simple', "title" || ' ' || coalesce("description", '') || ' ' || "body")"#;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Postgres {
+            return Ok(());
+        }
+
+        for (table, column) in UNSIGNED_COLUMNS {
+            execute(
+                manager,
+                format!(
+                    r#"ALTER TABLE "{table}" ALTER COLUMN "{column}" TYPE oid USING "{column}"::integer::oid"#
+                ),
+            )
+            .await?;
+        }
+
+        execute(
+            manager,
+            format!(
+                r#"CREATE INDEX "idx_post_search" ON "blog_posts" USING GIN ({})"#,
+                SEARCH_DOCUMENT
+            ),
+        )
+        .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Postgres {
+            return Ok(());
+        }
+
+        execute(manager, r#"DROP INDEX "idx_post_search""#.to_owned()).await?;
+
+        for (table, column) in UNSIGNED_COLUMNS.iter().rev() {
+            let int_type = if *column == "level" {
+                "smallint"
+            } else {
+                "integer"
+            };
+            execute(
+                manager,
+                format!(
+                    r#"ALTER TABLE "{table}" ALTER COLUMN "{column}" TYPE {int_type} USING "{column}"::bigint::{int_type}"#
+                ),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
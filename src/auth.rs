@@ -0,0 +1,219 @@
+use crate::{
+    entities::{prelude::*, users},
+    AppState,
+};
+use actix_session::{Session, SessionExt};
+use actix_web::{
+    dev::Payload,
+    error::{ErrorForbidden, ErrorInternalServerError, ErrorUnauthorized},
+    get,
+    http::header,
+    post, web, FromRequest, HttpRequest, HttpResponse, Result as ActixResult,
+};
+use sailfish::TemplateOnce;
+use sea_orm::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{future::Future, pin::Pin, str::FromStr};
+
+/// Session key that holds the id of the logged in user
+const USER_KEY: &str = "user_id";
+
+/// Permission levels stored in `users.level`.
+/// Each role can do everything the roles below it can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum Role {
+    Commenter = 0,
+    Author = 1,
+    Mod = 2,
+    Admin = 3,
+}
+
+impl From<u8> for Role {
+    fn from(level: u8) -> Self {
+        match level {
+            1 => Role::Author,
+            2 => Role::Mod,
+            3 => Role::Admin,
+            // Unknown levels get the least privileged role
+            _ => Role::Commenter,
+        }
+    }
+}
+
+/// For levels read from Postgres, see `users::Level`
+#[cfg(feature = "postgres")]
+impl From<u32> for Role {
+    fn from(level: u32) -> Self {
+        u8::try_from(level).map_or(Role::Commenter, Role::from)
+    }
+}
+
+impl Role {
+    /// The name used in the config and on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Commenter => "commenter",
+            Role::Author => "author",
+            Role::Mod => "mod",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        [Role::Commenter, Role::Author, Role::Mod, Role::Admin]
+            .into_iter()
+            .find(|role| role.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown role `{}`. Use commenter, author, mod or admin.",
+                    name
+                )
+            })
+    }
+}
+
+/// The user that owns the current session.
+/// Rejects the request with a 401 if nobody is logged in.
+/// Use `Option<CurrentUser>` for pages that anonymous readers can see.
+#[derive(Debug)]
+pub struct CurrentUser(pub users::Model);
+
+impl CurrentUser {
+    pub fn role(&self) -> Role {
+        Role::from(self.0.level)
+    }
+}
+
+impl FromRequest for CurrentUser {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let session = req.get_session();
+        let data = req.app_data::<web::Data<AppState>>().cloned();
+
+        Box::pin(async move {
+            let data = data.ok_or_else(|| ErrorInternalServerError("app state not configured"))?;
+            let user_id = session
+                .get::<u32>(USER_KEY)?
+                .ok_or_else(|| ErrorUnauthorized("not logged in"))?;
+
+            let user = Users::find_by_id(user_id)
+                .one(&data.db)
+                .await
+                .map_err(ErrorInternalServerError)?;
+
+            // The account may have been deleted since the session was issued
+            user.map(CurrentUser).ok_or_else(|| {
+                session.purge();
+                ErrorUnauthorized("not logged in")
+            })
+        })
+    }
+}
+
+/// A logged in user with at least the role `LEVEL`.
+/// Rejects the request with a 401 if nobody is logged in and a 403 if the user's level is too low.
+///
+/// ```ignore
+/// async fn handler(RequireRole(user): RequireRole<{ Role::Author as u8 }>) { ... }
+/// ```
+#[derive(Debug)]
+pub struct RequireRole<const LEVEL: u8>(pub users::Model);
+
+pub type AuthorUser = RequireRole<{ Role::Author as u8 }>;
+pub type ModUser = RequireRole<{ Role::Mod as u8 }>;
+
+impl<const LEVEL: u8> FromRequest for RequireRole<LEVEL> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let user = CurrentUser::from_request(req, payload);
+
+        Box::pin(async move {
+            let user = user.await?;
+            if user.role() >= Role::from(LEVEL) {
+                Ok(RequireRole(user.0))
+            } else {
+                Err(ErrorForbidden("insufficient permissions"))
+            }
+        })
+    }
+}
+
+pub fn redirect(location: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "login.stpl")]
+struct LoginTemplate {
+    error: Option<&'static str>,
+}
+
+#[get("/login")]
+async fn login_page(user: Option<CurrentUser>) -> ActixResult<HttpResponse> {
+    if user.is_some() {
+        return Ok(redirect("/"));
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html")
+        .body(LoginTemplate { error: None }.render_once().unwrap()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginForm {
+    username: String,
+    password: String,
+}
+
+#[post("/login")]
+async fn login(
+    data: web::Data<AppState>,
+    session: Session,
+    form: web::Form<LoginForm>,
+) -> ActixResult<HttpResponse> {
+    let user = Users::find()
+        .filter(users::Column::Username.eq(form.username.as_str()))
+        .one(&data.db)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let verified = match &user {
+        Some(user) => {
+            bcrypt::verify(&form.password, &user.password).map_err(ErrorInternalServerError)?
+        }
+        None => false,
+    };
+
+    Ok(if let Some(user) = user.filter(|_| verified) {
+        session.renew();
+        session.insert(USER_KEY, user.user_id)?;
+        redirect("/")
+    } else {
+        HttpResponse::Unauthorized().content_type("text/html").body(
+            LoginTemplate {
+                error: Some("Invalid username or password"),
+            }
+            .render_once()
+            .unwrap(),
+        )
+    })
+}
+
+/// A POST so other sites can't sign users out with a link or image
+#[post("/logout")]
+async fn logout(session: Session) -> ActixResult<HttpResponse> {
+    session.purge();
+    Ok(redirect("/"))
+}
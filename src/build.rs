@@ -0,0 +1,166 @@
+//! Writes the public pages of the blog to plain files, for serving without the app.
+//!
+//! Pages are written as `index.html` in a directory named after their url,
+//! so `/post/hello` becomes `post/hello/index.html`.
+
+use crate::{
+    comments::CommentBox,
+    config::CONFIG,
+    entities::{blog_posts, prelude::*, tags, users},
+    feeds::{render_feed, FeedFormat},
+    media::MEDIA_URL,
+    posts::{load_posts, published, CursorQuery, PostData, PostTemplate},
+    tags::tagged,
+};
+use sailfish::TemplateOnce;
+use sea_orm::{prelude::*, query::*};
+use std::{fs, io, path::Path};
+
+/// Where the stylesheets, fonts and scripts are served from
+const STATIC_DIR: &str = "./static";
+
+/// What a build wrote
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BuildSummary {
+    pub pages: usize,
+    pub posts: usize,
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+    fs::write(path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Writes the page served at `url` under `out`
+fn write_page(out: &Path, url: &str, html: &str) -> Result<(), String> {
+    write(&out.join(url.trim_matches('/')).join("index.html"), html)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes every page of the posts that match `filter`, starting at `page_url`,
+/// and returns how many there were
+async fn write_listing(
+    db: &DatabaseConnection,
+    out: &Path,
+    filter: Condition,
+    page_url: &str,
+    heading: Option<String>,
+) -> Result<usize, String> {
+    let mut pages = 0;
+    let mut before = None;
+    loop {
+        let cursor = before.map_or_else(CursorQuery::default, CursorQuery::before);
+        let (page, older) = load_posts(db, &cursor, filter.clone(), page_url, heading.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        let url = match before {
+            Some(id) => format!("{}/before/{}", page_url, id),
+            None => page_url.to_owned(),
+        };
+        write_page(out, &url, &page.render_once().map_err(|e| e.to_string())?)?;
+        pages += 1;
+
+        before = older;
+        if before.is_none() {
+            return Ok(pages);
+        }
+    }
+}
+
+/// Writes the feeds for the posts that match `filter` to `{dir}/feed.{extension}`
+async fn write_feeds(
+    db: &DatabaseConnection,
+    out: &Path,
+    filter: Condition,
+    subtitle: Option<String>,
+    dir: &str,
+) -> Result<(), String> {
+    for format in [FeedFormat::Rss, FeedFormat::Atom, FeedFormat::Json] {
+        let feed = render_feed(db, filter.clone(), subtitle.clone(), dir.to_owned(), format)
+            .await
+            .map_err(|e| e.to_string())?;
+        let file = format!("feed.{}", format.extension());
+        write(&out.join(dir).join(file), &feed)?;
+    }
+    Ok(())
+}
+
+/// Renders every page of posts, every published post, every tag and the feeds into `out`,
+/// and copies the static files and uploads next to them.
+/// Files already in `out` are overwritten, but never removed.
+pub async fn build_site(db: &DatabaseConnection, out: &Path) -> Result<BuildSummary, String> {
+    let mut summary = BuildSummary::default();
+
+    summary.pages += write_listing(db, out, Condition::all(), "/posts", None).await?;
+    // The home page is the first page of posts
+    fs::copy(out.join("posts/index.html"), out.join("index.html"))
+        .map_err(|e| format!("{}: {}", out.join("index.html").display(), e))?;
+
+    let tags = Tags::find()
+        .order_by_asc(tags::Column::TagId)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    for tag in tags {
+        let url = format!("/tag/{}", tag.slug);
+        let heading = format!("Tagged {}", tag.name);
+        summary.pages += write_listing(db, out, tagged(&tag), &url, Some(heading)).await?;
+        write_feeds(
+            db,
+            out,
+            tagged(&tag),
+            Some(tag.name),
+            url.trim_start_matches('/'),
+        )
+        .await?;
+    }
+
+    let posts = BlogPosts::find()
+        .column(users::Column::Username)
+        .column_as(users::Column::Level, "author_level")
+        .join(JoinType::InnerJoin, blog_posts::Relation::Users.def())
+        .filter(published())
+        .order_by_asc(blog_posts::Column::PostId)
+        .into_model::<PostData>()
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for post in posts {
+        let url = format!("/post/{}", post.slug);
+        let page = PostTemplate::load(db, post, false, None, CommentBox::default())
+            .await
+            .map_err(|e| e.to_string())?;
+        write_page(out, &url, &page.render_once().map_err(|e| e.to_string())?)?;
+        summary.posts += 1;
+    }
+
+    write_feeds(db, out, Condition::all(), None, "").await?;
+
+    copy_dir(Path::new(STATIC_DIR), &out.join("static"))
+        .map_err(|e| format!("Could not copy {}: {}", STATIC_DIR, e))?;
+    if CONFIG.media.dir.is_dir() {
+        copy_dir(
+            &CONFIG.media.dir,
+            &out.join(MEDIA_URL.trim_start_matches('/')),
+        )
+        .map_err(|e| format!("Could not copy {}: {}", CONFIG.media.dir.display(), e))?;
+    }
+
+    Ok(summary)
+}
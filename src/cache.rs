@@ -0,0 +1,117 @@
+//! Rendered post html, so markdown is only parsed again when a post changes.
+//!
+//! Entries are keyed by the post and when it was last updated. Edits that don't
+//! move `last_updated`, like imports, are caught by comparing a hash of the body.
+
+use crate::{
+    format::{render_preview, render_with_toc, Heading},
+    posts::PostData,
+};
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use sea_orm::prelude::DateTime;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    num::NonZeroUsize,
+    sync::Mutex,
+};
+
+/// How many posts are kept
+const CAPACITY: usize = 256;
+
+static CACHE: Lazy<Mutex<LruCache<Key, Entry>>> =
+    Lazy::new(|| Mutex::new(LruCache::new(NonZeroUsize::new(CAPACITY).unwrap())));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    post_id: u32,
+    last_updated: Option<DateTime>,
+}
+
+/// What was rendered from one version of a post
+struct Entry {
+    /// Hash of the markdown and whether its html was trusted
+    source: u64,
+    full: Option<(String, Option<Vec<Heading>>)>,
+    /// The number of words the preview was cut to, the preview and whether it was shortened
+    preview: Option<(usize, String, bool)>,
+}
+
+impl Entry {
+    fn new(source: u64) -> Self {
+        Entry {
+            source,
+            full: None,
+            preview: None,
+        }
+    }
+}
+
+fn key(post: &PostData) -> Key {
+    Key {
+        post_id: post.post_id,
+        last_updated: post.last_updated,
+    }
+}
+
+fn source(post: &PostData) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    post.body.hash(&mut hasher);
+    post.trusted_html().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Runs `f` on the entry for `post`, replacing it first if it was rendered from other markdown
+fn with_entry<T>(post: &PostData, f: impl FnOnce(&mut Entry) -> T) -> T {
+    let source = source(post);
+    let mut cache = CACHE.lock().unwrap();
+    let entry = cache.get_or_insert_mut(key(post), || Entry::new(source));
+    if entry.source != source {
+        *entry = Entry::new(source);
+    }
+    f(entry)
+}
+
+/// The html of a post and its table of contents, as `render_with_toc` gives them
+pub fn full(post: &PostData) -> (String, Option<Vec<Heading>>) {
+    if let Some(full) = with_entry(post, |entry| entry.full.clone()) {
+        return full;
+    }
+
+    // Rendering happens outside the lock so other workers aren't held up
+    let full = render_with_toc(&post.body, post.trusted_html());
+    with_entry(post, |entry| entry.full = Some(full.clone()));
+    full
+}
+
+/// The preview of a post cut to `words`, and whether anything was cut
+pub fn preview(post: &PostData, words: usize) -> (String, bool) {
+    let cached = with_entry(post, |entry| match &entry.preview {
+        Some((size, html, shortened)) if *size == words => Some((html.clone(), *shortened)),
+        _ => None,
+    });
+    if let Some(preview) = cached {
+        return preview;
+    }
+
+    let mut html = String::new();
+    let shortened = render_preview(&post.body, &mut html, words, post.trusted_html());
+    with_entry(post, |entry| {
+        entry.preview = Some((words, html.clone(), shortened))
+    });
+    (html, shortened)
+}
+
+/// Drops everything rendered for a post. Call whenever it's edited.
+pub fn invalidate(post_id: u32) {
+    let mut cache = CACHE.lock().unwrap();
+    let stale: Vec<Key> = cache
+        .iter()
+        .map(|(key, _)| *key)
+        .filter(|key| key.post_id == post_id)
+        .collect();
+    for key in stale {
+        cache.pop(&key);
+    }
+}
@@ -0,0 +1,277 @@
+//! Commands for running the blog from a shell instead of starting the server
+
+use crate::{
+    auth::Role,
+    build::build_site,
+    entities::{blog_posts, prelude::*, users},
+    export::{export_posts, import_posts},
+};
+use clap::Subcommand;
+use sea_orm::{entity::*, prelude::*, query::*, sea_query::Expr};
+use std::{
+    io::{self, BufRead, IsTerminal},
+    path::PathBuf,
+};
+
+/// Column limit from the migrations
+const USERNAME_LEN: usize = 60;
+
+/// The lowest cost bcrypt allows keeps the tests fast
+const HASH_COST: u32 = if cfg!(test) { 4 } else { bcrypt::DEFAULT_COST };
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Manage user accounts
+    #[clap(subcommand)]
+    User(UserCommand),
+    /// Write every post to a directory of markdown files
+    Export { dir: PathBuf },
+    /// Create or update posts from a directory written by export, matching them by slug.
+    /// Authors are matched by username.
+    Import { dir: PathBuf },
+    /// Render the published posts and feeds into a directory that any web server can serve.
+    /// Pages are written as index.html files, so the server has to look for those.
+    Build { dir: PathBuf },
+}
+
+impl Command {
+    /// Whether the command renders pages, which needs the config loaded
+    pub fn needs_config(&self) -> bool {
+        matches!(self, Command::Build { .. })
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UserCommand {
+    /// Create a user. The password is asked for, or read from stdin when it isn't a terminal.
+    Create {
+        username: String,
+        /// commenter, author, mod or admin
+        #[clap(long, default_value = "author")]
+        role: Role,
+    },
+    /// Set a new password for a user
+    Passwd { username: String },
+    /// Change what a user may do
+    Level {
+        username: String,
+        /// commenter, author, mod or admin
+        role: Role,
+    },
+    /// List every user
+    List,
+    /// Delete a user. Users with posts need somebody to take the posts over.
+    Delete {
+        username: String,
+        /// Give the user's posts to this user
+        #[clap(long, value_name = "USERNAME")]
+        reassign_to: Option<String>,
+    },
+}
+
+/// Runs `command`, returning a message for the user when it fails
+pub async fn run(db: &DatabaseConnection, command: Command) -> Result<(), String> {
+    match command {
+        Command::User(UserCommand::Create { username, role }) => {
+            let password = read_password()?;
+            let user = create_user(db, &username, &password, role).await?;
+            println!("Created {} as {}", user.username, role.name());
+        }
+        Command::User(UserCommand::Passwd { username }) => {
+            let user = find_user(db, &username).await?;
+            let password = read_password()?;
+            set_password(db, user, &password).await?;
+            println!("Changed the password of {}", username);
+        }
+        Command::User(UserCommand::Level { username, role }) => {
+            let user = find_user(db, &username).await?;
+            set_role(db, user, role).await?;
+            println!("{} is now {}", username, role.name());
+        }
+        Command::User(UserCommand::List) => {
+            println!("{:>6}  {:<10}  {:<10}  Username", "Id", "Role", "Joined");
+            for user in list_users(db).await? {
+                println!(
+                    "{:>6}  {:<10}  {:<10}  {}",
+                    user.user_id,
+                    Role::from(user.level).name(),
+                    user.join_date.format("%Y-%m-%d").to_string(),
+                    user.username
+                );
+            }
+        }
+        Command::User(UserCommand::Delete {
+            username,
+            reassign_to,
+        }) => {
+            let moved = delete_user(db, &username, reassign_to.as_deref()).await?;
+            match reassign_to {
+                Some(to) if moved > 0 => {
+                    println!("Deleted {} and gave {} posts to {}", username, moved, to)
+                }
+                _ => println!("Deleted {}", username),
+            }
+        }
+        Command::Export { dir } => {
+            let count = export_posts(db, &dir).await?;
+            println!("Exported {} posts to {}", count, dir.display());
+        }
+        Command::Import { dir } => {
+            let summary = import_posts(db, &dir).await?;
+            println!(
+                "Created {} and updated {} posts",
+                summary.created, summary.updated
+            );
+        }
+        Command::Build { dir } => {
+            let summary = build_site(db, &dir).await?;
+            println!(
+                "Wrote {} pages of posts and {} posts to {}",
+                summary.pages,
+                summary.posts,
+                dir.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Asks for a password twice on a terminal. Otherwise reads the first line of stdin.
+fn read_password() -> Result<String, String> {
+    let password = if io::stdin().is_terminal() {
+        let password = rpassword::prompt_password("Password: ").map_err(|e| e.to_string())?;
+        let repeated =
+            rpassword::prompt_password("Repeat password: ").map_err(|e| e.to_string())?;
+        if password != repeated {
+            return Err("The passwords don't match".to_owned());
+        }
+        password
+    } else {
+        let mut line = String::new();
+        io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?;
+        line.trim_end_matches(['\r', '\n']).to_owned()
+    };
+
+    if password.is_empty() {
+        Err("The password may not be empty".to_owned())
+    } else {
+        Ok(password)
+    }
+}
+
+fn hash(password: &str) -> Result<String, String> {
+    bcrypt::hash(password, HASH_COST).map_err(|e| e.to_string())
+}
+
+pub async fn find_user(db: &DatabaseConnection, username: &str) -> Result<users::Model, String> {
+    Users::find()
+        .filter(users::Column::Username.eq(username))
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("There is no user called {}", username))
+}
+
+pub async fn create_user(
+    db: &DatabaseConnection,
+    username: &str,
+    password: &str,
+    role: Role,
+) -> Result<users::Model, String> {
+    let username = username.trim();
+    if username.is_empty() || username.chars().count() > USERNAME_LEN {
+        return Err(format!(
+            "Usernames have to be 1 to {} characters long",
+            USERNAME_LEN
+        ));
+    }
+    if find_user(db, username).await.is_ok() {
+        return Err(format!("{} is already taken", username));
+    }
+
+    users::ActiveModel {
+        username: Set(username.to_owned()),
+        password: Set(hash(password)?),
+        join_date: Set(chrono::Utc::now().naive_utc()),
+        level: Set(role as users::Level),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn set_password(
+    db: &DatabaseConnection,
+    user: users::Model,
+    password: &str,
+) -> Result<(), String> {
+    let mut user: users::ActiveModel = user.into();
+    user.password = Set(hash(password)?);
+    user.update(db).await.map(|_| ()).map_err(|e| e.to_string())
+}
+
+pub async fn set_role(
+    db: &DatabaseConnection,
+    user: users::Model,
+    role: Role,
+) -> Result<(), String> {
+    let mut user: users::ActiveModel = user.into();
+    user.level = Set(role as users::Level);
+    user.update(db).await.map(|_| ()).map_err(|e| e.to_string())
+}
+
+pub async fn list_users(db: &DatabaseConnection) -> Result<Vec<users::Model>, String> {
+    Users::find()
+        .order_by_asc(users::Column::UserId)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Deletes a user and returns how many posts were moved to `reassign_to`.
+/// Posts are deleted along with their author, so users with posts are only
+/// deleted when someone takes them over.
+pub async fn delete_user(
+    db: &DatabaseConnection,
+    username: &str,
+    reassign_to: Option<&str>,
+) -> Result<usize, String> {
+    let user = find_user(db, username).await?;
+    let posts = BlogPosts::find()
+        .filter(blog_posts::Column::AuthorId.eq(user.user_id))
+        .count(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let to = match reassign_to {
+        _ if posts == 0 => None,
+        Some(to) if to == username => {
+            return Err("Posts can't be given to the user being deleted".to_owned())
+        }
+        Some(to) => Some(find_user(db, to).await?),
+        None => {
+            return Err(format!(
+                "{} has {} posts, which would be deleted too. Give them to someone else with --reassign-to.",
+                username, posts
+            ))
+        }
+    };
+
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    if let Some(to) = to {
+        BlogPosts::update_many()
+            .col_expr(blog_posts::Column::AuthorId, Expr::value(to.user_id))
+            .filter(blog_posts::Column::AuthorId.eq(user.user_id))
+            .exec(&txn)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    user.delete(&txn).await.map_err(|e| e.to_string())?;
+    txn.commit().await.map_err(|e| e.to_string())?;
+    Ok(posts)
+}
@@ -0,0 +1,304 @@
+use crate::{
+    auth::{redirect, CurrentUser, ModUser, Role},
+    entities::{blog_posts, comments, prelude::*, users},
+    format::render_comment,
+    posts::{readable_by, render_post},
+    AppState,
+};
+use actix_web::{
+    error::{ErrorBadRequest, ErrorInternalServerError},
+    get, post, web, HttpRequest, HttpResponse, Result as ActixResult,
+};
+use comments::CommentStatus;
+use sailfish::TemplateOnce;
+use sea_orm::{
+    entity::*,
+    prelude::*,
+    query::*,
+    sea_query::{Expr, SimpleExpr},
+    FromQueryResult,
+};
+use serde::Deserialize;
+
+/// Column limit from the migrations
+const NAME_LEN: usize = 60;
+/// Keeps comments to a readable length. The column itself is unlimited.
+const BODY_LEN: usize = 5000;
+
+#[derive(FromQueryResult, Debug)]
+pub struct CommentData {
+    pub comment_id: u32,
+    pub anon_name: Option<String>,
+    pub username: Option<String>,
+    pub body: String,
+    pub comment_date: DateTime,
+}
+
+impl CommentData {
+    /// Members are shown by username and guests by the name they entered
+    pub fn author(&self) -> &str {
+        self.username
+            .as_deref()
+            .or(self.anon_name.as_deref())
+            .unwrap_or("Anonymous")
+    }
+}
+
+/// Loads the approved comments on a post, oldest first, with their bodies rendered
+pub async fn post_comments(db: &DatabaseConnection, post_id: u32) -> ActixResult<Vec<CommentData>> {
+    let comments = Comments::find()
+        .select_only()
+        .column(comments::Column::CommentId)
+        .column(comments::Column::AnonName)
+        .column(comments::Column::Body)
+        .column(comments::Column::CommentDate)
+        .column(users::Column::Username)
+        .join(JoinType::LeftJoin, comments::Relation::Users.def())
+        .filter(comments::Column::PostId.eq(post_id))
+        .filter(comments::Column::Status.eq(CommentStatus::Approved))
+        .order_by_asc(comments::Column::CommentDate)
+        .order_by_asc(comments::Column::CommentId)
+        .into_model::<CommentData>()
+        .all(db)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .map(|mut comment| {
+            comment.body = render_comment(&comment.body);
+            comment
+        })
+        .collect();
+
+    Ok(comments)
+}
+
+/// The comment form shown under a post
+#[derive(Debug, Default)]
+pub struct CommentBox {
+    pub form: CommentForm,
+    pub error: Option<String>,
+    /// The reader's last comment is waiting for a mod to approve it
+    pub pending: bool,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct CommentForm {
+    #[serde(default)]
+    pub name: String,
+    pub body: String,
+}
+
+impl CommentForm {
+    fn validate(&self, user: Option<&CurrentUser>) -> Result<(), String> {
+        let name_len = self.name.trim().chars().count();
+        let body_len = self.body.trim().chars().count();
+
+        if user.is_none() && name_len == 0 {
+            Err("Please enter a name".to_owned())
+        } else if user.is_none() && name_len > NAME_LEN {
+            Err(format!(
+                "Names may not be longer than {} characters",
+                NAME_LEN
+            ))
+        } else if body_len == 0 {
+            Err("Comments may not be empty".to_owned())
+        } else if body_len > BODY_LEN {
+            Err(format!(
+                "Comments may not be longer than {} characters",
+                BODY_LEN
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[post("/post/{id}/comments")]
+async fn add_comment(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    user: Option<CurrentUser>,
+    id: web::Path<u32>,
+    form: web::Form<CommentForm>,
+) -> ActixResult<HttpResponse> {
+    let form = form.into_inner();
+    let post = BlogPosts::find_by_id(*id)
+        .filter(readable_by(user.as_ref()))
+        .one(&data.db)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let post = match (post, form.validate(user.as_ref())) {
+        (Some(post), Ok(())) => post,
+        // Show the post again with the error, or a 404 if it doesn't exist
+        (_, res) => {
+            let comment_box = CommentBox {
+                form,
+                error: res.err(),
+                pending: false,
+            };
+            let filter = blog_posts::Column::PostId.eq(*id);
+            return render_post(&req, &data.db, filter, user.as_ref(), comment_box).await;
+        }
+    };
+
+    let (author_id, anon_name) = match &user {
+        Some(CurrentUser(user)) => (Some(user.user_id), None),
+        None => (None, Some(form.name.trim().to_owned())),
+    };
+
+    // Authors and above are trusted to comment without review
+    let status = match &user {
+        Some(user) if user.role() >= Role::Author => CommentStatus::Approved,
+        _ => CommentStatus::Pending,
+    };
+
+    let comment = comments::ActiveModel {
+        post_id: Set(post.post_id),
+        author_id: Set(author_id),
+        anon_name: Set(anon_name),
+        body: Set(form.body.trim().to_owned()),
+        comment_date: Set(chrono::Utc::now().naive_utc()),
+        status: Set(status),
+        ..Default::default()
+    }
+    .insert(&data.db)
+    .await
+    .map_err(ErrorInternalServerError)?;
+
+    Ok(redirect(&if status == CommentStatus::Approved {
+        format!("/post/{}#comment-{}", post.slug, comment.comment_id)
+    } else {
+        format!("/post/{}?pending=true#comment-form", post.slug)
+    }))
+}
+
+/// Number of comments shown in the moderation queue at once
+const QUEUE_SIZE: u64 = 100;
+
+#[derive(FromQueryResult, Debug)]
+struct QueuedComment {
+    comment_id: u32,
+    slug: String,
+    title: String,
+    anon_name: Option<String>,
+    username: Option<String>,
+    body: String,
+    comment_date: DateTime,
+}
+
+impl QueuedComment {
+    fn author(&self) -> &str {
+        self.username
+            .as_deref()
+            .or(self.anon_name.as_deref())
+            .unwrap_or("Anonymous")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QueueQuery {
+    status: Option<CommentStatus>,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "moderation.stpl")]
+struct ModerationTemplate {
+    status: CommentStatus,
+    comments: Vec<QueuedComment>,
+}
+
+#[get("/admin/comments")]
+async fn comment_queue(
+    data: web::Data<AppState>,
+    _user: ModUser,
+    query: web::Query<QueueQuery>,
+) -> ActixResult<HttpResponse> {
+    let status = query.status.unwrap_or(CommentStatus::Pending);
+
+    let comments = Comments::find()
+        .select_only()
+        .column(comments::Column::CommentId)
+        .column(blog_posts::Column::Slug)
+        .column(blog_posts::Column::Title)
+        .column(comments::Column::AnonName)
+        .column(comments::Column::Body)
+        .column(comments::Column::CommentDate)
+        .column(users::Column::Username)
+        .join(JoinType::InnerJoin, comments::Relation::BlogPosts.def())
+        .join(JoinType::LeftJoin, comments::Relation::Users.def())
+        .filter(comments::Column::Status.eq(status))
+        .order_by_asc(comments::Column::CommentDate)
+        .limit(QUEUE_SIZE)
+        .into_model::<QueuedComment>()
+        .all(&data.db)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .map(|mut comment| {
+            comment.body = render_comment(&comment.body);
+            comment
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(
+        ModerationTemplate { status, comments }
+            .render_once()
+            .unwrap(),
+    ))
+}
+
+/// Applies the action to every checked comment in the queue.
+/// The form is read as raw pairs because each checkbox submits its own `comment` field.
+#[post("/admin/comments")]
+async fn moderate_comments(
+    data: web::Data<AppState>,
+    _user: ModUser,
+    query: web::Query<QueueQuery>,
+    form: web::Form<Vec<(String, String)>>,
+) -> ActixResult<HttpResponse> {
+    let mut action = None;
+    let mut ids = Vec::new();
+
+    for (key, value) in form.into_inner() {
+        match key.as_str() {
+            "action" => action = Some(value),
+            "comment" => ids.push(value.parse::<u32>().map_err(ErrorBadRequest)?),
+            _ => {}
+        }
+    }
+
+    let status = query.status.unwrap_or(CommentStatus::Pending);
+    let back = redirect(&format!("/admin/comments?status={}", status.to_value()));
+    if ids.is_empty() {
+        return Ok(back);
+    }
+
+    let cond = comments::Column::CommentId.is_in(ids);
+    let res = match action.as_deref() {
+        Some("approve") => set_status(&data.db, cond, CommentStatus::Approved).await,
+        Some("reject") => set_status(&data.db, cond, CommentStatus::Spam).await,
+        Some("delete") => Comments::delete_many()
+            .filter(cond)
+            .exec(&data.db)
+            .await
+            .map(|_| ()),
+        _ => return Err(ErrorBadRequest("unknown action")),
+    };
+    res.map_err(ErrorInternalServerError)?;
+
+    Ok(back)
+}
+
+async fn set_status(
+    db: &DatabaseConnection,
+    cond: SimpleExpr,
+    status: CommentStatus,
+) -> Result<(), DbErr> {
+    Comments::update_many()
+        .col_expr(comments::Column::Status, Expr::value(status))
+        .filter(cond)
+        .exec(db)
+        .await
+        .map(|_| ())
+}
@@ -0,0 +1,111 @@
+//! Validators for conditional requests, so clients that already have a page
+//! or feed get an empty 304 instead of the whole thing again.
+
+use actix_web::{
+    http::header::{
+        self, CacheControl, CacheDirective, EntityTag, HttpDate, IfModifiedSince, IfNoneMatch,
+    },
+    HttpMessage, HttpRequest, HttpResponse,
+};
+use sea_orm::prelude::DateTime;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    time::{Duration, SystemTime},
+};
+
+/// An `ETag` and `Last-Modified` for a response
+pub struct Validators {
+    etag: EntityTag,
+    last_modified: Option<HttpDate>,
+    /// The response is different for signed in users
+    vary_cookie: bool,
+}
+
+fn http_date(date: DateTime) -> HttpDate {
+    let secs = u64::try_from(date.and_utc().timestamp()).unwrap_or(0);
+    HttpDate::from(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+impl Validators {
+    /// `last_modified` is the newest date the response was built from, and
+    /// `version` is everything else that changes it, like post ids and bodies.
+    pub fn new(last_modified: Option<DateTime>, version: impl Hash) -> Self {
+        let mut hasher = DefaultHasher::new();
+        version.hash(&mut hasher);
+        last_modified.hash(&mut hasher);
+
+        Validators {
+            // Weak since the same content could be compressed differently
+            etag: EntityTag::new_weak(format!("{:016x}", hasher.finish())),
+            last_modified: last_modified.map(http_date),
+            vary_cookie: false,
+        }
+    }
+
+    /// Marks the response as depending on the session cookie, so shared caches
+    /// don't hand a signed in user's page to anyone else or the other way around
+    pub fn vary_cookie(mut self) -> Self {
+        self.vary_cookie = true;
+        self
+    }
+
+    /// Whether the client's copy is still current. `If-None-Match` wins over
+    /// `If-Modified-Since` when both are sent.
+    fn fresh(&self, req: &HttpRequest) -> bool {
+        match req.get_header::<IfNoneMatch>() {
+            Some(IfNoneMatch::Any) => true,
+            Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&self.etag)),
+            None => match (req.get_header::<IfModifiedSince>(), self.last_modified) {
+                (Some(IfModifiedSince(since)), Some(modified)) => {
+                    SystemTime::from(modified) <= SystemTime::from(since)
+                }
+                _ => false,
+            },
+        }
+    }
+
+    /// Replies with a 304 when the client has this version already,
+    /// and otherwise with the output of `body`
+    pub fn respond(
+        &self,
+        req: &HttpRequest,
+        cache_control: CacheControl,
+        content_type: &str,
+        body: impl FnOnce() -> String,
+    ) -> HttpResponse {
+        let fresh = self.fresh(req);
+        let mut res = if fresh {
+            HttpResponse::NotModified()
+        } else {
+            HttpResponse::Ok()
+        };
+        res.insert_header(header::ETag(self.etag.clone()))
+            .insert_header(cache_control);
+        if let Some(modified) = self.last_modified {
+            res.insert_header(header::LastModified(modified));
+        }
+        if self.vary_cookie {
+            res.insert_header((header::VARY, "Cookie"));
+        }
+
+        if fresh {
+            res.finish()
+        } else {
+            res.content_type(content_type).body(body())
+        }
+    }
+}
+
+/// Lets browsers and proxies keep a response for `max_age` seconds
+pub fn public(max_age: u32) -> CacheControl {
+    CacheControl(vec![
+        CacheDirective::Public,
+        CacheDirective::MaxAge(max_age),
+    ])
+}
+
+/// For pages made for a signed in user, which only their browser should keep
+pub fn private() -> CacheControl {
+    CacheControl(vec![CacheDirective::Private, CacheDirective::NoCache])
+}
@@ -1,5 +1,19 @@
-use lazy_static::lazy_static;
+use crate::auth::Role;
+use once_cell::sync::OnceCell;
 use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+/// Files that are tried in order when no `--config` path is given
+const DEFAULT_PATHS: [&str; 2] = ["./config.json", "./config.toml"];
+
+/// Prefix of the environment variables that override config fields
+const ENV_PREFIX: &str = "B5_";
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Webmaster {
@@ -7,6 +21,127 @@ pub struct Webmaster {
     pub name: String,
 }
 
+/// Where and how the server listens
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Server {
+    pub address: String,
+    pub port: u16,
+    /// Defaults to the number of CPUs
+    pub workers: Option<usize>,
+    /// Seconds to keep idle connections open. 0 turns keep-alive off.
+    pub keep_alive: Option<u64>,
+    /// Serve HTTPS with this certificate and key
+    pub tls: Option<Tls>,
+    /// Listen on a Unix domain socket instead of `address` and `port`
+    pub unix_socket: Option<PathBuf>,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Server {
+            address: "127.0.0.1".into(),
+            port: 8080,
+            workers: None,
+            keep_alive: None,
+            tls: None,
+            unix_socket: None,
+        }
+    }
+}
+
+/// PEM files for rustls
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Tls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Uploaded files
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Media {
+    /// Where uploads are stored. Served under `/media`.
+    pub dir: PathBuf,
+    /// Largest upload in bytes
+    pub max_size: usize,
+    /// Images wider than this get a thumbnail this wide
+    pub thumbnail_width: u32,
+}
+
+impl Default for Media {
+    fn default() -> Self {
+        Media {
+            dir: "./media".into(),
+            max_size: 10 * 1024 * 1024,
+            thumbnail_width: 640,
+        }
+    }
+}
+
+/// How long clients and proxies may keep responses, in seconds.
+/// Pages and feeds can still be revalidated with their `ETag` and `Last-Modified`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Cache {
+    /// Post pages and listings
+    pub html_max_age: u32,
+    /// RSS, Atom and JSON feeds
+    pub feed_max_age: u32,
+    /// Files under `/static`
+    pub static_max_age: u32,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Cache {
+            html_max_age: 0,
+            feed_max_age: 300,
+            static_max_age: 24 * 60 * 60,
+        }
+    }
+}
+
+/// Markdown extensions for posts and comments. All of them are off unless turned on.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Markdown {
+    pub tables: bool,
+    pub footnotes: bool,
+    /// `- [ ]` and `- [x]` list items become checkboxes
+    pub task_lists: bool,
+    /// Curly quotes, dashes and ellipses
+    pub smart_punctuation: bool,
+    /// Posts with at least this many headings start with a table of contents
+    pub toc_min_headings: Option<usize>,
+}
+
+/// Raw html in posts. Everything else, comments included, has its html escaped.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Html {
+    /// Posts by users with at least this role may use the allowed html
+    pub trusted_role: Option<Role>,
+    /// Tags allowed on top of the sanitizer's safe defaults, such as `details`
+    pub tags: Vec<String>,
+    /// Attributes allowed on each tag, on top of the defaults
+    pub attributes: BTreeMap<String, Vec<String>>,
+    /// Hosts that `iframe`s may embed pages from over https
+    pub iframe_hosts: Vec<String>,
+}
+
+/// Tags the sanitizer can't allow. It removes scripts and styles along with
+/// their content, and doesn't support SVG animation.
+const UNSAFE_TAGS: &[&str] = &[
+    "script",
+    "style",
+    "animate",
+    "animateColor",
+    "animateMotion",
+    "animateTransform",
+    "set",
+];
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
     pub title: String,
@@ -18,6 +153,16 @@ pub struct Config {
     pub page_size: u64,
     pub rss_size: u64,
     pub lang: String,
+    #[serde(default)]
+    pub server: Server,
+    #[serde(default)]
+    pub media: Media,
+    #[serde(default)]
+    pub cache: Cache,
+    #[serde(default)]
+    pub markdown: Markdown,
+    #[serde(default)]
+    pub html: Html,
 }
 
 impl Config {
@@ -26,12 +171,437 @@ impl Config {
         let mut rng = rand::thread_rng();
         self.taglines.choose(&mut rng)
     }
+
+    /// Checks the values that parse fine but would break the site
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, error: &str| {
+            if !ok {
+                errors.push(error.to_owned());
+            }
+        };
+
+        check(!self.title.trim().is_empty(), "`title` may not be empty");
+        check(
+            self.url.starts_with("http://") || self.url.starts_with("https://"),
+            "`url` must start with http:// or https://",
+        );
+        check(self.url.ends_with('/'), "`url` must end with a slash");
+        check(
+            self.webmaster.email.contains('@'),
+            "`webmaster.email` must be an email address",
+        );
+        check(!self.taglines.is_empty(), "`taglines` may not be empty");
+        check(self.preview_size > 0, "`preview_size` must be at least 1");
+        check(self.page_size > 0, "`page_size` must be at least 1");
+        check(self.rss_size > 0, "`rss_size` must be at least 1");
+        check(!self.lang.trim().is_empty(), "`lang` may not be empty");
+
+        let server = &self.server;
+        check(
+            server.workers != Some(0),
+            "`server.workers` must be at least 1",
+        );
+        if let Some(tls) = &server.tls {
+            check(
+                tls.cert.is_file(),
+                "`server.tls.cert` is not a file that can be read",
+            );
+            check(
+                tls.key.is_file(),
+                "`server.tls.key` is not a file that can be read",
+            );
+        }
+        if let Some(path) = &server.unix_socket {
+            check(cfg!(unix), "`server.unix_socket` is only supported on Unix");
+            check(
+                server.tls.is_none(),
+                "`server.unix_socket` can't be used with `server.tls`. Let the proxy in front handle TLS.",
+            );
+            // Only sockets are removed before binding, so nothing else gets deleted by mistake
+            #[cfg(unix)]
+            check(
+                path.symlink_metadata().is_err() || is_socket(path),
+                "`server.unix_socket` is a file that isn't a socket",
+            );
+        }
+
+        let media = &self.media;
+        check(
+            !media.dir.is_file(),
+            "`media.dir` must be a directory, not a file",
+        );
+        check(media.max_size > 0, "`media.max_size` must be at least 1");
+        check(
+            media.thumbnail_width > 0,
+            "`media.thumbnail_width` must be at least 1",
+        );
+
+        check(
+            self.markdown.toc_min_headings != Some(0),
+            "`markdown.toc_min_headings` must be at least 1",
+        );
+
+        let html = &self.html;
+        for tag in html.tags.iter().chain(html.attributes.keys()) {
+            check(
+                !UNSAFE_TAGS.contains(&tag.as_str()),
+                &format!("`html` can't allow the {} tag", tag),
+            );
+        }
+        // Links get their `rel` from the sanitizer
+        check(
+            !html
+                .attributes
+                .get("a")
+                .is_some_and(|attributes| attributes.iter().any(|a| a == "rel")),
+            "`html.attributes` can't allow `rel` on links",
+        );
+
+        errors
+    }
+}
+
+/// Whether `path` is a unix socket, such as one left behind by the last run
+#[cfg(unix)]
+pub fn is_socket(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    path.symlink_metadata()
+        .is_ok_and(|meta| meta.file_type().is_socket())
+}
+
+/// How an environment variable is turned into a config value
+#[derive(Clone, Copy)]
+enum EnvKind {
+    Text,
+    Number,
+    /// `true` or `false`
+    Bool,
+    /// Items separated by `|`
+    List,
+    /// `key=item,item` entries separated by `;`
+    Map,
+}
+
+/// A config field and the environment variable that overrides it
+struct Field {
+    /// Variable name after `ENV_PREFIX`
+    env: &'static str,
+    path: &'static [&'static str],
+    kind: EnvKind,
+    /// Fields without a default have to be set in the file or the environment
+    required: bool,
+}
+
+const fn field(env: &'static str, path: &'static [&'static str], kind: EnvKind) -> Field {
+    Field {
+        env,
+        path,
+        kind,
+        required: true,
+    }
+}
+
+/// A field with a default value
+const fn optional(env: &'static str, path: &'static [&'static str], kind: EnvKind) -> Field {
+    Field {
+        required: false,
+        ..field(env, path, kind)
+    }
+}
+
+const FIELDS: &[Field] = &[
+    field("TITLE", &["title"], EnvKind::Text),
+    field("URL", &["url"], EnvKind::Text),
+    field("DESCRIPTION", &["description"], EnvKind::Text),
+    field("WEBMASTER_EMAIL", &["webmaster", "email"], EnvKind::Text),
+    field("WEBMASTER_NAME", &["webmaster", "name"], EnvKind::Text),
+    field("TAGLINES", &["taglines"], EnvKind::List),
+    field("PREVIEW_SIZE", &["preview_size"], EnvKind::Number),
+    field("PAGE_SIZE", &["page_size"], EnvKind::Number),
+    field("RSS_SIZE", &["rss_size"], EnvKind::Number),
+    field("LANG", &["lang"], EnvKind::Text),
+    optional("SERVER_ADDRESS", &["server", "address"], EnvKind::Text),
+    optional("SERVER_PORT", &["server", "port"], EnvKind::Number),
+    optional("SERVER_WORKERS", &["server", "workers"], EnvKind::Number),
+    optional(
+        "SERVER_KEEP_ALIVE",
+        &["server", "keep_alive"],
+        EnvKind::Number,
+    ),
+    optional("SERVER_TLS_CERT", &["server", "tls", "cert"], EnvKind::Text),
+    optional("SERVER_TLS_KEY", &["server", "tls", "key"], EnvKind::Text),
+    optional(
+        "SERVER_UNIX_SOCKET",
+        &["server", "unix_socket"],
+        EnvKind::Text,
+    ),
+    optional("MEDIA_DIR", &["media", "dir"], EnvKind::Text),
+    optional("MEDIA_MAX_SIZE", &["media", "max_size"], EnvKind::Number),
+    optional(
+        "MEDIA_THUMBNAIL_WIDTH",
+        &["media", "thumbnail_width"],
+        EnvKind::Number,
+    ),
+    optional(
+        "CACHE_HTML_MAX_AGE",
+        &["cache", "html_max_age"],
+        EnvKind::Number,
+    ),
+    optional(
+        "CACHE_FEED_MAX_AGE",
+        &["cache", "feed_max_age"],
+        EnvKind::Number,
+    ),
+    optional(
+        "CACHE_STATIC_MAX_AGE",
+        &["cache", "static_max_age"],
+        EnvKind::Number,
+    ),
+    optional("MARKDOWN_TABLES", &["markdown", "tables"], EnvKind::Bool),
+    optional(
+        "MARKDOWN_FOOTNOTES",
+        &["markdown", "footnotes"],
+        EnvKind::Bool,
+    ),
+    optional(
+        "MARKDOWN_TASK_LISTS",
+        &["markdown", "task_lists"],
+        EnvKind::Bool,
+    ),
+    optional(
+        "MARKDOWN_SMART_PUNCTUATION",
+        &["markdown", "smart_punctuation"],
+        EnvKind::Bool,
+    ),
+    optional(
+        "MARKDOWN_TOC_MIN_HEADINGS",
+        &["markdown", "toc_min_headings"],
+        EnvKind::Number,
+    ),
+    optional(
+        "HTML_TRUSTED_ROLE",
+        &["html", "trusted_role"],
+        EnvKind::Text,
+    ),
+    optional("HTML_TAGS", &["html", "tags"], EnvKind::List),
+    optional("HTML_ATTRIBUTES", &["html", "attributes"], EnvKind::Map),
+    optional(
+        "HTML_IFRAME_HOSTS",
+        &["html", "iframe_hosts"],
+        EnvKind::List,
+    ),
+];
+
+/// Every problem found while loading the config
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for error in &self.0 {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Parses a JSON or TOML config file, picked by its extension
+fn read_file(path: &Path) -> Result<Value, String> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+
+    let value = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str::<Value>(&raw).map_err(|e| e.to_string()),
+        _ => serde_json::from_str::<Value>(&raw).map_err(|e| e.to_string()),
+    };
+    value.map_err(|e| format!("Could not parse {}: {}", path.display(), e))
+}
+
+/// Overrides the fields in `value` that have a variable in `vars`
+fn apply_env(
+    value: &mut Value,
+    vars: impl Iterator<Item = (String, String)>,
+    errors: &mut Vec<String>,
+) {
+    for (key, raw) in vars {
+        let field = key
+            .strip_prefix(ENV_PREFIX)
+            .and_then(|name| FIELDS.iter().find(|field| field.env == name));
+        let field = match field {
+            Some(field) => field,
+            None => continue,
+        };
+
+        let new_value = match field.kind {
+            EnvKind::Text => Value::from(raw),
+            EnvKind::List => raw.split('|').map(str::trim).collect(),
+            EnvKind::Number => match raw.trim().parse::<u64>() {
+                Ok(n) => Value::from(n),
+                Err(_) => {
+                    errors.push(format!("{} must be a whole number", key));
+                    continue;
+                }
+            },
+            EnvKind::Bool => match raw.trim().parse::<bool>() {
+                Ok(b) => Value::from(b),
+                Err(_) => {
+                    errors.push(format!("{} must be true or false", key));
+                    continue;
+                }
+            },
+            EnvKind::Map => match parse_map(&raw) {
+                Some(map) => Value::Object(map),
+                None => {
+                    errors.push(format!("{} must look like key=a,b;other=c", key));
+                    continue;
+                }
+            },
+        };
+
+        let (last, parents) = field.path.split_last().unwrap();
+        let mut target = &mut *value;
+        for name in parents {
+            target = object(target)
+                .entry(*name)
+                .or_insert_with(|| Value::Object(Map::new()));
+        }
+        object(target).insert((*last).to_owned(), new_value);
+    }
+}
+
+/// Parses `key=item,item` entries separated by `;`
+fn parse_map(raw: &str) -> Option<Map<String, Value>> {
+    let mut map = Map::new();
+    for entry in raw
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (key, items) = entry.split_once('=')?;
+        let key = key.trim();
+        if key.is_empty() {
+            return None;
+        }
+        let items = items
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect();
+        map.insert(key.to_owned(), items);
+    }
+    Some(map)
 }
 
-lazy_static! {
-    pub static ref CONFIG: Config = {
-        let raw_config =
-            std::fs::read_to_string("./config.json").expect("Could not read config.json");
-        serde_json::from_str::<Config>(&raw_config).expect("Could not parse config.json")
+/// `value` as an object, replacing it with an empty one if it's something else
+fn object(value: &mut Value) -> &mut Map<String, Value> {
+    if !value.is_object() {
+        *value = Value::Object(Map::new());
+    }
+    value.as_object_mut().unwrap()
+}
+
+/// Reads the config from `path`, or the first default path that exists,
+/// with environment variables from `vars` on top
+pub fn load_from(
+    path: Option<&Path>,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<Config, ConfigError> {
+    let path = path.or_else(|| {
+        DEFAULT_PATHS
+            .iter()
+            .map(Path::new)
+            .find(|path| path.exists())
+    });
+
+    // Everything can come from the environment, so the file is optional
+    let mut value = match path {
+        Some(path) => read_file(path).map_err(|e| ConfigError(vec![e]))?,
+        None => Value::Object(Map::new()),
     };
+
+    let mut errors = Vec::new();
+    apply_env(&mut value, vars, &mut errors);
+
+    for field in FIELDS.iter().filter(|field| field.required) {
+        if field
+            .path
+            .iter()
+            .try_fold(&value, |v, name| v.get(name))
+            .is_none()
+        {
+            errors.push(format!(
+                "`{}` is missing. Set it in the config file or with {}{}.",
+                field.path.join("."),
+                ENV_PREFIX,
+                field.env
+            ));
+        }
+    }
+
+    let config = serde_json::from_value::<Config>(value);
+    match &config {
+        Ok(config) => errors.extend(config.validate()),
+        // Missing fields have already been reported
+        Err(e) if errors.is_empty() => errors.push(e.to_string()),
+        Err(_) => {}
+    }
+
+    match config {
+        Ok(config) if errors.is_empty() => Ok(config),
+        _ => Err(ConfigError(errors)),
+    }
+}
+
+static LOADED: OnceCell<Config> = OnceCell::new();
+
+/// Loads and checks the config. Has to run before `CONFIG` is used.
+pub fn init(path: Option<&Path>) -> Result<&'static Config, ConfigError> {
+    // Variables that aren't unicode can't be config values, and `env::vars` would panic on them
+    let vars = std::env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)));
+    let config = load_from(path, vars)?;
+    Ok(LOADED.get_or_init(|| config))
+}
+
+/// The config loaded by `init`
+pub struct GlobalConfig;
+
+pub static CONFIG: GlobalConfig = GlobalConfig;
+
+impl Deref for GlobalConfig {
+    type Target = Config;
+
+    #[cfg(not(test))]
+    fn deref(&self) -> &Config {
+        LOADED
+            .get()
+            .expect("config::init should run when the server starts")
+    }
+
+    /// Tests run against the sample so they don't depend on the local setup.
+    /// They upload to the temp dir instead of the repo, have every markdown extension on,
+    /// and trust mods with html.
+    #[cfg(test)]
+    fn deref(&self) -> &Config {
+        LOADED.get_or_init(|| {
+            let media_dir = std::env::temp_dir().join(format!("b5-media-{}", std::process::id()));
+            let vars = [
+                ("MEDIA_DIR", media_dir.display().to_string()),
+                ("MARKDOWN_TABLES", "true".to_owned()),
+                ("MARKDOWN_FOOTNOTES", "true".to_owned()),
+                ("MARKDOWN_TASK_LISTS", "true".to_owned()),
+                ("MARKDOWN_SMART_PUNCTUATION", "true".to_owned()),
+                ("MARKDOWN_TOC_MIN_HEADINGS", "3".to_owned()),
+                ("HTML_TRUSTED_ROLE", "mod".to_owned()),
+                ("HTML_IFRAME_HOSTS", "www.youtube-nocookie.com".to_owned()),
+            ]
+            .map(|(key, value)| (format!("{}{}", ENV_PREFIX, key), value));
+            load_from(Some(Path::new("./sample_config.json")), vars.into_iter())
+                .expect("sample_config.json should be valid")
+        })
+    }
 }
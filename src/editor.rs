@@ -0,0 +1,409 @@
+use crate::{
+    auth::{redirect, AuthorUser, RequireRole, Role},
+    cache,
+    entities::{
+        blog_posts::{self, PostStatus},
+        prelude::*,
+        users,
+    },
+    revisions::save_revision,
+    slug::{is_slug_clash, record_old_slug, unique_post_slug},
+    tags::{set_post_tags, tags_for_post},
+    AppState,
+};
+use actix_web::{
+    error::{ErrorForbidden, ErrorInternalServerError, ErrorNotFound},
+    get, post, web, HttpResponse, Result as ActixResult,
+};
+use sailfish::TemplateOnce;
+use sea_orm::{entity::*, prelude::*, query::*};
+use serde::Deserialize;
+
+/// Column limits from the migrations
+pub const TITLE_LEN: usize = 100;
+pub const DESCRIPTION_LEN: usize = 200;
+
+/// Format sent by `datetime-local` inputs
+const DATE_INPUT_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Shown when another post was saved with the same slug in the meantime
+const SLUG_TAKEN: &str = "Another post just took that slug. Please save again.";
+
+#[derive(Debug, Deserialize, Default)]
+pub struct PostForm {
+    title: String,
+    description: String,
+    body: String,
+    /// Comma separated tag names
+    #[serde(default)]
+    tags: String,
+    /// Generated from the title when left blank
+    #[serde(default)]
+    slug: String,
+    /// Unchecked posts are saved as drafts
+    #[serde(default)]
+    publish: bool,
+    /// When the post goes public, in UTC. Left blank to publish right away.
+    #[serde(default)]
+    post_date: String,
+    /// Words shown in previews. Left blank for the configured default.
+    #[serde(default)]
+    preview_size: String,
+}
+
+impl PostForm {
+    fn validate(&self) -> Result<(), String> {
+        if self.title.trim().is_empty() {
+            Err("A title is required".to_owned())
+        } else if self.title.chars().count() > TITLE_LEN {
+            Err(format!(
+                "The title may not be longer than {} characters",
+                TITLE_LEN
+            ))
+        } else if self.description.chars().count() > DESCRIPTION_LEN {
+            Err(format!(
+                "The description may not be longer than {} characters",
+                DESCRIPTION_LEN
+            ))
+        } else {
+            self.post_date()?;
+            self.preview_size()?;
+            Ok(())
+        }
+    }
+
+    fn preview_size(&self) -> Result<Option<u32>, &'static str> {
+        match self.preview_size.trim() {
+            "" => Ok(None),
+            size => size
+                .parse::<u32>()
+                .ok()
+                .filter(|size| *size > 0)
+                .map(Some)
+                .ok_or("The preview length must be a whole number of words"),
+        }
+    }
+
+    fn post_date(&self) -> Result<Option<DateTime>, &'static str> {
+        match self.post_date.trim() {
+            "" => Ok(None),
+            // Browsers leave the seconds off when they're zero
+            date => DateTime::parse_from_str(date, DATE_INPUT_FORMAT)
+                .or_else(|_| DateTime::parse_from_str(date, "%Y-%m-%dT%H:%M"))
+                .map(Some)
+                .map_err(|_| "The publish date is not a valid date"),
+        }
+    }
+
+    /// Works out the status and date to save the post with.
+    /// Published posts with a date in the future are scheduled.
+    /// When editing `saved`, a blank date keeps the date it went live on,
+    /// and an unchanged date keeps its stored precision.
+    fn schedule(&self, saved: Option<&blog_posts::Model>) -> (PostStatus, DateTime) {
+        let now = now();
+        let unchanged = saved
+            .map(|post| post.post_date)
+            .filter(|date| date.format(DATE_INPUT_FORMAT).to_string() == self.post_date.trim());
+        let live_since = saved
+            .filter(|post| current_status(post) == PostStatus::Published)
+            .map(|post| post.post_date);
+        let date = unchanged
+            .or_else(|| self.post_date().ok().flatten())
+            .or(live_since)
+            .unwrap_or(now);
+
+        let status = if !self.publish {
+            PostStatus::Draft
+        } else if date > now {
+            PostStatus::Scheduled
+        } else {
+            PostStatus::Published
+        };
+
+        (status, date)
+    }
+
+    fn description(&self) -> Option<String> {
+        Some(self.description.trim())
+            .filter(|d| !d.is_empty())
+            .map(String::from)
+    }
+
+    async fn load(db: &DatabaseConnection, post: blog_posts::Model) -> ActixResult<Self> {
+        let tags = tags_for_post(db, post.post_id)
+            .await
+            .map_err(ErrorInternalServerError)?
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // Drafts get their date when they're published
+        let (publish, post_date) = match post.status {
+            PostStatus::Draft => (false, String::new()),
+            _ => (true, post.post_date.format(DATE_INPUT_FORMAT).to_string()),
+        };
+
+        Ok(Self {
+            title: post.title,
+            description: post.description.unwrap_or_default(),
+            body: post.body,
+            tags,
+            slug: post.slug,
+            publish,
+            post_date,
+            preview_size: post
+                .preview_size
+                .map(|size| size.to_string())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "editor.stpl")]
+struct EditorTemplate {
+    /// Slug of the post being edited
+    editing: Option<String>,
+    form: PostForm,
+    error: Option<String>,
+}
+
+impl EditorTemplate {
+    fn response(self) -> HttpResponse {
+        let mut res = if self.error.is_some() {
+            HttpResponse::BadRequest()
+        } else {
+            HttpResponse::Ok()
+        };
+        res.content_type("text/html")
+            .body(self.render_once().unwrap())
+    }
+}
+
+/// Authors may edit their own posts. Mods and admins may edit anyone's.
+fn can_edit(user: &users::Model, post: &blog_posts::Model) -> bool {
+    post.author_id == user.user_id || Role::from(user.level) >= Role::Mod
+}
+
+pub async fn find_editable(
+    db: &DatabaseConnection,
+    user: &users::Model,
+    id: u32,
+) -> ActixResult<blog_posts::Model> {
+    let post = BlogPosts::find_by_id(id)
+        .one(db)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("not found"))?;
+
+    if can_edit(user, &post) {
+        Ok(post)
+    } else {
+        Err(ErrorForbidden("insufficient permissions"))
+    }
+}
+
+fn now() -> DateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+/// The status a post has right now. Scheduled posts go live once their date
+/// passes without being saved again, so their stored status can be behind.
+fn current_status(post: &blog_posts::Model) -> PostStatus {
+    match post.status {
+        PostStatus::Scheduled if post.post_date <= now() => PostStatus::Published,
+        status => status,
+    }
+}
+
+#[get("/admin/posts/new")]
+async fn new_post_page(_user: AuthorUser) -> ActixResult<HttpResponse> {
+    Ok(EditorTemplate {
+        editing: None,
+        form: PostForm::default(),
+        error: None,
+    }
+    .response())
+}
+
+#[post("/admin/posts/new")]
+async fn new_post(
+    data: web::Data<AppState>,
+    RequireRole(user): AuthorUser,
+    form: web::Form<PostForm>,
+) -> ActixResult<HttpResponse> {
+    let form = form.into_inner();
+    if let Err(error) = form.validate() {
+        return Ok(EditorTemplate {
+            editing: None,
+            form,
+            error: Some(error),
+        }
+        .response());
+    }
+
+    let slug_source = if form.slug.trim().is_empty() {
+        &form.title
+    } else {
+        &form.slug
+    };
+    let slug = unique_post_slug(&data.db, slug_source, None)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let (status, post_date) = form.schedule(None);
+    let txn = data.db.begin().await.map_err(ErrorInternalServerError)?;
+    let post = blog_posts::ActiveModel {
+        slug: Set(slug),
+        title: Set(form.title.trim().to_owned()),
+        description: Set(form.description()),
+        preview_size: Set(form.preview_size().ok().flatten()),
+        body: Set(form.body.clone()),
+        author_id: Set(user.user_id),
+        post_date: Set(post_date),
+        status: Set(status),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await;
+    let post = match post {
+        Ok(post) => post,
+        Err(e) if is_slug_clash(&e) => {
+            return Ok(EditorTemplate {
+                editing: None,
+                form,
+                error: Some(SLUG_TAKEN.to_owned()),
+            }
+            .response())
+        }
+        Err(e) => return Err(ErrorInternalServerError(e)),
+    };
+
+    set_post_tags(&txn, post.post_id, &form.tags)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    save_revision(&txn, &post, user.user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    txn.commit().await.map_err(ErrorInternalServerError)?;
+
+    Ok(redirect(&format!("/post/{}", post.slug)))
+}
+
+#[get("/admin/posts/{id}/edit")]
+async fn edit_post_page(
+    data: web::Data<AppState>,
+    RequireRole(user): AuthorUser,
+    id: web::Path<u32>,
+) -> ActixResult<HttpResponse> {
+    let post = find_editable(&data.db, &user, *id).await?;
+
+    Ok(EditorTemplate {
+        editing: Some(post.slug.clone()),
+        form: PostForm::load(&data.db, post).await?,
+        error: None,
+    }
+    .response())
+}
+
+#[post("/admin/posts/{id}/edit")]
+async fn edit_post(
+    data: web::Data<AppState>,
+    RequireRole(user): AuthorUser,
+    id: web::Path<u32>,
+    form: web::Form<PostForm>,
+) -> ActixResult<HttpResponse> {
+    let post = find_editable(&data.db, &user, *id).await?;
+    let form = form.into_inner();
+
+    if let Err(error) = form.validate() {
+        return Ok(EditorTemplate {
+            editing: Some(post.slug),
+            form,
+            error: Some(error),
+        }
+        .response());
+    }
+
+    // Keep the old slug unless it was changed so that links don't break
+    let slug = match form.slug.trim() {
+        "" => post.slug.clone(),
+        slug if slug == post.slug => post.slug.clone(),
+        slug => unique_post_slug(&data.db, slug, Some(post.post_id))
+            .await
+            .map_err(ErrorInternalServerError)?,
+    };
+
+    // Links to a post that was public keep working after its slug changes
+    let old_slug =
+        (slug != post.slug && post.status != PostStatus::Draft).then(|| post.slug.clone());
+    let editing = post.slug.clone();
+
+    let (status, post_date) = form.schedule(Some(&post));
+    let mut post: blog_posts::ActiveModel = post.into();
+    post.slug = Set(slug);
+    post.status = Set(status);
+    post.post_date = Set(post_date);
+    post.title = Set(form.title.trim().to_owned());
+    post.description = Set(form.description());
+    post.preview_size = Set(form.preview_size().ok().flatten());
+    post.body = Set(form.body.clone());
+    post.last_updated = Set(Some(now()));
+
+    let txn = data.db.begin().await.map_err(ErrorInternalServerError)?;
+    let post = match post.update(&txn).await {
+        Ok(post) => post,
+        Err(e) if is_slug_clash(&e) => {
+            return Ok(EditorTemplate {
+                editing: Some(editing),
+                form,
+                error: Some(SLUG_TAKEN.to_owned()),
+            }
+            .response())
+        }
+        Err(e) => return Err(ErrorInternalServerError(e)),
+    };
+    if let Some(old_slug) = old_slug {
+        record_old_slug(&txn, &old_slug, &post.slug, post.post_id)
+            .await
+            .map_err(ErrorInternalServerError)?;
+    }
+    set_post_tags(&txn, post.post_id, &form.tags)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    save_revision(&txn, &post, user.user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    txn.commit().await.map_err(ErrorInternalServerError)?;
+    cache::invalidate(post.post_id);
+
+    Ok(redirect(&format!("/post/{}", post.slug)))
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "admin_posts.stpl")]
+struct AdminPostsTemplate {
+    posts: Vec<blog_posts::Model>,
+}
+
+/// Lists the posts a user can edit, including drafts and scheduled posts
+#[get("/admin/posts")]
+async fn admin_posts(
+    data: web::Data<AppState>,
+    RequireRole(user): AuthorUser,
+) -> ActixResult<HttpResponse> {
+    let mut query = BlogPosts::find().order_by_desc(blog_posts::Column::PostId);
+    if Role::from(user.level) < Role::Mod {
+        query = query.filter(blog_posts::Column::AuthorId.eq(user.user_id));
+    }
+
+    let posts = query
+        .all(&data.db)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html")
+        .body(AdminPostsTemplate { posts }.render_once().unwrap()))
+}
@@ -1,6 +1,20 @@
 //! SeaORM Entity. Generated by sea-orm-codegen 0.9.1
 
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize, Serialize)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(9))")]
+#[serde(rename_all = "lowercase")]
+pub enum PostStatus {
+    #[sea_orm(string_value = "draft")]
+    Draft,
+    /// Goes public once `post_date` has passed
+    #[sea_orm(string_value = "scheduled")]
+    Scheduled,
+    #[sea_orm(string_value = "published")]
+    Published,
+}
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
 #[sea_orm(table_name = "blog_posts")]
@@ -14,6 +28,11 @@ pub struct Model {
     pub post_date: DateTime,
     pub description: Option<String>,
     pub last_updated: Option<DateTime>,
+    #[sea_orm(unique)]
+    pub slug: String,
+    pub status: PostStatus,
+    /// Words shown in previews, instead of the configured `preview_size`
+    pub preview_size: Option<u32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -28,6 +47,12 @@ pub enum Relation {
     Users,
     #[sea_orm(has_many = "super::comments::Entity")]
     Comments,
+    #[sea_orm(has_many = "super::old_slugs::Entity")]
+    OldSlugs,
+    #[sea_orm(has_many = "super::post_revisions::Entity")]
+    PostRevisions,
+    #[sea_orm(has_many = "super::post_tags::Entity")]
+    PostTags,
 }
 
 impl Related<super::users::Entity> for Entity {
@@ -42,4 +67,31 @@ impl Related<super::comments::Entity> for Entity {
     }
 }
 
+impl Related<super::old_slugs::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OldSlugs.def()
+    }
+}
+
+impl Related<super::post_tags::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PostTags.def()
+    }
+}
+
+impl Related<super::tags::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::post_tags::Relation::Tags.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(super::post_tags::Relation::BlogPosts.def().rev())
+    }
+}
+
+impl Related<super::post_revisions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PostRevisions.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -1,6 +1,19 @@
 //! SeaORM Entity. Generated by sea-orm-codegen 0.9.1
 
 use sea_orm::entity::prelude::*;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(8))")]
+#[serde(rename_all = "lowercase")]
+pub enum CommentStatus {
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    #[sea_orm(string_value = "approved")]
+    Approved,
+    #[sea_orm(string_value = "spam")]
+    Spam,
+}
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
 #[sea_orm(table_name = "comments")]
@@ -13,6 +26,7 @@ pub struct Model {
     #[sea_orm(column_type = "Text")]
     pub body: String,
     pub comment_date: DateTime,
+    pub status: CommentStatus,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -4,4 +4,9 @@ pub mod prelude;
 
 pub mod blog_posts;
 pub mod comments;
+pub mod media;
+pub mod old_slugs;
+pub mod post_revisions;
+pub mod post_tags;
+pub mod tags;
 pub mod users;
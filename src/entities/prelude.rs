@@ -2,4 +2,9 @@
 
 pub use super::blog_posts::Entity as BlogPosts;
 pub use super::comments::Entity as Comments;
+pub use super::media::Entity as Media;
+pub use super::old_slugs::Entity as OldSlugs;
+pub use super::post_revisions::Entity as PostRevisions;
+pub use super::post_tags::Entity as PostTags;
+pub use super::tags::Entity as Tags;
 pub use super::users::Entity as Users;
@@ -11,15 +11,26 @@ pub struct Model {
     pub username: String,
     pub password: String,
     pub join_date: DateTime,
-    pub level: u8,
+    pub level: Level,
 }
 
+/// The type levels are read as. Postgres has no one byte unsigned type, so the column
+/// is an `oid` there, which sea-orm can only read as a `u32`.
+#[cfg(not(feature = "postgres"))]
+pub type Level = u8;
+#[cfg(feature = "postgres")]
+pub type Level = u32;
+
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::blog_posts::Entity")]
     BlogPosts,
     #[sea_orm(has_many = "super::comments::Entity")]
     Comments,
+    #[sea_orm(has_many = "super::media::Entity")]
+    Media,
+    #[sea_orm(has_many = "super::post_revisions::Entity")]
+    PostRevisions,
 }
 
 impl Related<super::blog_posts::Entity> for Entity {
@@ -34,4 +45,16 @@ impl Related<super::comments::Entity> for Entity {
     }
 }
 
+impl Related<super::media::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Media.def()
+    }
+}
+
+impl Related<super::post_revisions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PostRevisions.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
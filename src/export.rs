@@ -0,0 +1,293 @@
+//! Moves posts between the database and a directory of markdown files.
+//!
+//! Each post is saved as `{slug}.md`, starting with TOML front matter between `+++` lines:
+//!
+//! ```text
+//! +++
+//! title = "Hello"
+//! slug = "hello"
+//! author = "jane"
+//! status = "published"
+//! post_date = "2022-01-01T12:00:00"
+//! +++
+//!
+//! The body, as written in the editor.
+//! ```
+
+use crate::{
+    cache,
+    editor::{DESCRIPTION_LEN, TITLE_LEN},
+    entities::{blog_posts, prelude::*, users},
+    revisions::save_revision,
+    slug::{slugify, POST_SLUG_LEN},
+    tags::{set_post_tags, tags_for_post},
+};
+use chrono::NaiveDateTime;
+use sea_orm::{entity::*, prelude::*, query::*};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::Path};
+
+const FENCE: &str = "+++";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FrontMatter {
+    title: String,
+    slug: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// Username of the author
+    author: String,
+    status: blog_posts::PostStatus,
+    post_date: NaiveDateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_updated: Option<NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    preview_size: Option<u32>,
+}
+
+/// What an import did
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub updated: usize,
+}
+
+/// Joins front matter and a body into the contents of a file
+fn to_file(front: &FrontMatter, body: &str) -> Result<String, String> {
+    let front = toml::to_string(front).map_err(|e| e.to_string())?;
+    Ok(format!("{}\n{}{}\n\n{}", FENCE, front, FENCE, body))
+}
+
+/// Splits a file into its front matter and body
+fn from_file(text: &str) -> Result<(FrontMatter, &str), String> {
+    let missing = || {
+        format!(
+            "The file has to start with front matter between {} lines",
+            FENCE
+        )
+    };
+    let mut lines = text.split_inclusive('\n');
+
+    let first = lines.next().unwrap_or_default();
+    if first.trim_end() != FENCE {
+        return Err(missing());
+    }
+    let start = first.len();
+
+    let mut end = start;
+    for line in lines {
+        if line.trim_end() == FENCE {
+            let front = toml::from_str(&text[start..end]).map_err(|e| e.to_string())?;
+            let body = &text[end + line.len()..];
+            // The blank line written after the front matter isn't part of the body
+            let body = body
+                .strip_prefix("\r\n")
+                .or_else(|| body.strip_prefix('\n'))
+                .unwrap_or(body);
+            return Ok((front, body));
+        }
+        end += line.len();
+    }
+    Err(missing())
+}
+
+/// Writes every post to `dir` and returns how many there were
+pub async fn export_posts(db: &DatabaseConnection, dir: &Path) -> Result<usize, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+    let posts = BlogPosts::find()
+        .find_also_related(Users)
+        .order_by_asc(blog_posts::Column::PostId)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for (post, author) in &posts {
+        let tags = tags_for_post(db, post.post_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        let front = FrontMatter {
+            title: post.title.clone(),
+            slug: post.slug.clone(),
+            description: post.description.clone(),
+            author: author
+                .as_ref()
+                .map(|author| author.username.clone())
+                .unwrap_or_default(),
+            status: post.status,
+            post_date: post.post_date,
+            last_updated: post.last_updated,
+            tags: tags.into_iter().map(|tag| tag.name).collect(),
+            preview_size: post.preview_size,
+        };
+
+        let path = dir.join(format!("{}.md", post.slug));
+        fs::write(&path, to_file(&front, &post.body)?)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    Ok(posts.len())
+}
+
+/// Checks what the editor would, plus the fields only an import can set
+fn check(front: &FrontMatter) -> Result<(), String> {
+    if front.title.trim().is_empty() {
+        Err("The title may not be empty".to_owned())
+    } else if front.title.chars().count() > TITLE_LEN {
+        Err(format!(
+            "The title may not be longer than {} characters",
+            TITLE_LEN
+        ))
+    } else if front
+        .description
+        .as_ref()
+        .is_some_and(|description| description.chars().count() > DESCRIPTION_LEN)
+    {
+        Err(format!(
+            "The description may not be longer than {} characters",
+            DESCRIPTION_LEN
+        ))
+    } else if front.slug != slugify(&front.slug, POST_SLUG_LEN)
+        || front.slug.is_empty()
+        || front.slug.chars().all(|c| c.is_ascii_digit())
+    {
+        Err(format!(
+            "`{}` is not a valid slug. Use lowercase words joined by dashes.",
+            front.slug
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Upserts every `.md` file in `dir`, matching posts by slug.
+/// Nothing is written unless every file can be imported.
+pub async fn import_posts(db: &DatabaseConnection, dir: &Path) -> Result<ImportSummary, String> {
+    let mut paths = fs::read_dir(dir)
+        .map_err(|e| format!("{}: {}", dir.display(), e))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    paths.retain(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "md"));
+    paths.sort();
+
+    let mut authors: HashMap<String, users::Model> = HashMap::new();
+    let mut posts = Vec::new();
+    let mut errors = Vec::new();
+
+    for path in paths {
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                errors.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+        let (front, body) = match from_file(&text).and_then(|(front, body)| {
+            check(&front)?;
+            Ok((front, body.to_owned()))
+        }) {
+            Ok(post) => post,
+            Err(e) => {
+                errors.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+
+        if posts
+            .iter()
+            .any(|(other, _): &(FrontMatter, String)| other.slug == front.slug)
+        {
+            errors.push(format!(
+                "{}: another file also has the slug {}",
+                path.display(),
+                front.slug
+            ));
+            continue;
+        }
+        if !authors.contains_key(&front.author) {
+            let author = Users::find()
+                .filter(users::Column::Username.eq(front.author.as_str()))
+                .one(db)
+                .await
+                .map_err(|e| e.to_string())?;
+            match author {
+                Some(author) => {
+                    authors.insert(front.author.clone(), author);
+                }
+                None => {
+                    errors.push(format!(
+                        "{}: there is no user called {}",
+                        path.display(),
+                        front.author
+                    ));
+                    continue;
+                }
+            }
+        }
+        posts.push((front, body));
+    }
+
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    // One transaction, so a failure part way leaves the database as it was
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    let mut summary = ImportSummary::default();
+    let mut imported = Vec::new();
+    for (front, body) in posts {
+        let author_id = authors[&front.author].user_id;
+        let existing = BlogPosts::find()
+            .filter(blog_posts::Column::Slug.eq(front.slug.as_str()))
+            .one(&txn)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let content_changed = existing.as_ref().is_none_or(|post| {
+            post.title != front.title || post.description != front.description || post.body != body
+        });
+        let exists = existing.is_some();
+        let mut post = match existing {
+            Some(post) => post.into_active_model(),
+            None => blog_posts::ActiveModel {
+                slug: Set(front.slug),
+                ..Default::default()
+            },
+        };
+        post.title = Set(front.title);
+        post.description = Set(front.description);
+        post.body = Set(body);
+        post.author_id = Set(author_id);
+        post.status = Set(front.status);
+        post.post_date = Set(front.post_date);
+        post.last_updated = Set(front.last_updated);
+        post.preview_size = Set(front.preview_size);
+        let post = if exists {
+            summary.updated += 1;
+            post.update(&txn).await
+        } else {
+            summary.created += 1;
+            post.insert(&txn).await
+        }
+        .map_err(|e| e.to_string())?;
+
+        set_post_tags(&txn, post.post_id, &front.tags.join(","))
+            .await
+            .map_err(|e| e.to_string())?;
+        if content_changed {
+            save_revision(&txn, &post, author_id)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        imported.push(post.post_id);
+    }
+    txn.commit().await.map_err(|e| e.to_string())?;
+
+    for post_id in imported {
+        cache::invalidate(post_id);
+    }
+    Ok(summary)
+}
@@ -0,0 +1,275 @@
+use crate::{
+    cache,
+    conditional::{self, Validators},
+    config::CONFIG,
+    entities::{blog_posts, prelude::*, users},
+    posts::{published, PostData},
+    AppState,
+};
+use actix_web::{
+    error::ErrorInternalServerError, get, web, HttpRequest, HttpResponse, Result as ActixResult,
+};
+use sailfish::TemplateOnce;
+use sea_orm::{prelude::*, query::*};
+use serde::Serialize;
+
+/// Timestamp format used by Atom and JSON Feed. Post dates are stored in UTC.
+const RFC3339: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+#[derive(Debug, Clone, Copy)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+    Json,
+}
+
+impl FeedFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "rss",
+            FeedFormat::Atom => "atom",
+            FeedFormat::Json => "json",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "application/rss+xml",
+            FeedFormat::Atom => "application/atom+xml",
+            FeedFormat::Json => "application/feed+json",
+        }
+    }
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "rss.stpl")]
+struct RssTemplate {
+    posts: Vec<PostData>,
+    title: String,
+    link: String,
+    feed_link: String,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "atom.stpl")]
+struct AtomTemplate {
+    posts: Vec<PostData>,
+    title: String,
+    link: String,
+    feed_link: String,
+    updated: DateTime,
+}
+
+/// See https://www.jsonfeed.org/version/1.1/
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    description: &'a str,
+    language: &'a str,
+    authors: Vec<JsonAuthor<'a>>,
+    items: Vec<JsonItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonAuthor<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonItem<'a> {
+    id: String,
+    url: String,
+    title: &'a str,
+    content_html: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    date_published: String,
+    date_modified: String,
+    authors: Vec<JsonAuthor<'a>>,
+}
+
+fn json_feed(posts: &[PostData], title: String, link: String, feed_link: String) -> String {
+    let items = posts
+        .iter()
+        .map(|post| {
+            let url = format!("{}post/{}", CONFIG.url, post.slug);
+            JsonItem {
+                id: url.clone(),
+                url,
+                title: &post.title,
+                content_html: &post.body,
+                summary: post.description.as_deref(),
+                date_published: post.post_date.format(RFC3339).to_string(),
+                date_modified: post.updated().format(RFC3339).to_string(),
+                authors: vec![JsonAuthor {
+                    name: &post.username,
+                }],
+            }
+        })
+        .collect();
+
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title,
+        home_page_url: link,
+        feed_url: feed_link,
+        description: &CONFIG.description,
+        language: &CONFIG.lang,
+        authors: vec![JsonAuthor {
+            name: &CONFIG.webmaster.name,
+        }],
+        items,
+    };
+
+    serde_json::to_string(&feed).expect("feed should always serialize")
+}
+
+/// Builds a feed of the latest published posts that match `filter`.
+/// `path` is the page the feed mirrors, relative to `CONFIG.url`.
+pub async fn feed(
+    req: &HttpRequest,
+    db: &DatabaseConnection,
+    filter: Condition,
+    subtitle: Option<String>,
+    path: String,
+    format: FeedFormat,
+) -> ActixResult<HttpResponse> {
+    let posts = feed_posts(db, filter).await?;
+    let last_modified = posts.iter().map(PostData::updated).max();
+    let version = posts
+        .iter()
+        .map(|post| (post.post_id, &post.body))
+        .collect::<Vec<_>>();
+
+    Ok(Validators::new(last_modified, version).respond(
+        req,
+        conditional::public(CONFIG.cache.feed_max_age),
+        format.content_type(),
+        || render(posts, subtitle, path, format),
+    ))
+}
+
+/// Renders the body of the feed served by `feed`
+pub async fn render_feed(
+    db: &DatabaseConnection,
+    filter: Condition,
+    subtitle: Option<String>,
+    path: String,
+    format: FeedFormat,
+) -> ActixResult<String> {
+    let posts = feed_posts(db, filter).await?;
+    Ok(render(posts, subtitle, path, format))
+}
+
+/// The latest published posts that match `filter`, with their bodies rendered
+async fn feed_posts(db: &DatabaseConnection, filter: Condition) -> ActixResult<Vec<PostData>> {
+    let posts = BlogPosts::find()
+        .filter(filter)
+        .filter(published())
+        .order_by_desc(blog_posts::Column::PostId)
+        .limit(CONFIG.rss_size)
+        .column(users::Column::Username)
+        .column_as(users::Column::Level, "author_level")
+        .join(JoinType::InnerJoin, blog_posts::Relation::Users.def())
+        .into_model::<PostData>()
+        .all(db)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .map(|mut post| {
+            post.body = cache::full(&post).0;
+            post
+        })
+        .collect();
+    Ok(posts)
+}
+
+fn render(
+    posts: Vec<PostData>,
+    subtitle: Option<String>,
+    path: String,
+    format: FeedFormat,
+) -> String {
+    let title = match subtitle {
+        Some(subtitle) => format!("{}: {}", CONFIG.title, subtitle),
+        None => CONFIG.title.clone(),
+    };
+    let link = format!("{}{}", CONFIG.url, path);
+    let feed_link = if path.is_empty() {
+        format!("{}feed.{}", CONFIG.url, format.extension())
+    } else {
+        format!("{}/feed.{}", link, format.extension())
+    };
+
+    match format {
+        FeedFormat::Rss => RssTemplate {
+            posts,
+            title,
+            link,
+            feed_link,
+        }
+        .render_once()
+        .unwrap(),
+        FeedFormat::Atom => {
+            // Atom requires a date even when there is nothing in the feed
+            let updated = posts
+                .iter()
+                .map(PostData::updated)
+                .max()
+                .unwrap_or_else(|| chrono::Utc::now().naive_utc());
+
+            AtomTemplate {
+                posts,
+                title,
+                link,
+                feed_link,
+                updated,
+            }
+            .render_once()
+            .unwrap()
+        }
+        FeedFormat::Json => json_feed(&posts, title, link, feed_link),
+    }
+}
+
+#[get("/feed.rss")]
+async fn rss(req: HttpRequest, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    feed(
+        &req,
+        &data.db,
+        Condition::all(),
+        None,
+        String::new(),
+        FeedFormat::Rss,
+    )
+    .await
+}
+
+#[get("/feed.atom")]
+async fn atom(req: HttpRequest, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    feed(
+        &req,
+        &data.db,
+        Condition::all(),
+        None,
+        String::new(),
+        FeedFormat::Atom,
+    )
+    .await
+}
+
+#[get("/feed.json")]
+async fn json(req: HttpRequest, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    feed(
+        &req,
+        &data.db,
+        Condition::all(),
+        None,
+        String::new(),
+        FeedFormat::Json,
+    )
+    .await
+}
@@ -1,7 +1,148 @@
-use super::config::CONFIG;
+use super::{config::CONFIG, slug::slugify};
+use once_cell::sync::Lazy;
 use pulldown_cmark::{
-    html::push_html, CowStr, Event as MarkEvent, InlineStr, Options as MarkOption, Parser,
+    html::push_html, CodeBlockKind, CowStr, Event as MarkEvent, Options as MarkOption, Parser, Tag,
 };
+use std::collections::HashSet;
+use syntect::{
+    html::{ClassStyle, ClassedHTMLGenerator},
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+/// Allows the html that markdown itself produces, with ammonia's default url schemes
+fn markdown_sanitizer() -> ammonia::Builder<'static> {
+    let mut builder = ammonia::Builder::default();
+    builder
+        // Highlighted code, footnotes and heading anchors
+        .add_generic_attributes(["class", "id"])
+        // Task list checkboxes
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"]);
+    builder
+}
+
+/// Cleans the html of untrusted posts and comments, so links and images can't run scripts
+static STRICT_SANITIZER: Lazy<ammonia::Builder<'static>> = Lazy::new(markdown_sanitizer);
+
+/// Cleans the html of trusted posts, built from the `html` config
+static SANITIZER: Lazy<ammonia::Builder<'static>> = Lazy::new(|| {
+    let html = &CONFIG.html;
+    let mut builder = markdown_sanitizer();
+    builder
+        .add_tags(&html.tags)
+        .attribute_filter(|element, attribute, value| {
+            if element == "iframe" && attribute == "src" && !embeddable(value) {
+                None
+            } else {
+                Some(value.into())
+            }
+        });
+    for (tag, attributes) in &html.attributes {
+        builder.add_tag_attributes(tag.as_str(), attributes);
+    }
+    if !html.iframe_hosts.is_empty() {
+        builder.add_tags(["iframe"]).add_tag_attributes(
+            "iframe",
+            [
+                "src",
+                "width",
+                "height",
+                "title",
+                "allow",
+                "allowfullscreen",
+            ],
+        );
+    }
+    builder
+});
+
+/// Whether an iframe may load `src`
+fn embeddable(src: &str) -> bool {
+    let url = match ammonia::Url::parse(src) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+    url.scheme() == "https"
+        && url.host_str().is_some_and(|host| {
+            CONFIG
+                .html
+                .iframe_hosts
+                .iter()
+                .any(|allowed| allowed == host)
+        })
+}
+
+static SYNTAXES: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+
+/// Highlighted code gets classes like `hl-keyword`, styled by `static/highlight.css`
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// The language in a fence's info string, such as `rust` or `py,ignore`
+fn fence_lang(info: &str) -> &str {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+}
+
+/// Renders a code block as spans with highlighting classes
+fn highlight(syntax: &SyntaxReference, lang: &str, code: &str) -> Option<String> {
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAXES, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        generator
+            .parse_html_for_line_which_includes_newline(line)
+            .ok()?;
+    }
+
+    Some(format!(
+        "<pre class=\"hl-code\"><code class=\"language-{}\">{}</code></pre>\n",
+        html_escape::encode_double_quoted_attribute(lang),
+        generator.finalize()
+    ))
+}
+
+/// Replaces fenced code blocks in a language we know with highlighted html.
+/// Has to run after raw html is escaped, since the highlighted code is html itself.
+fn highlight_code<'a>(
+    events: impl Iterator<Item = MarkEvent<'a>>,
+) -> impl Iterator<Item = MarkEvent<'a>> {
+    let mut block: Option<(&SyntaxReference, String)> = None;
+
+    events.flat_map(move |event| match event {
+        MarkEvent::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref info))) => {
+            let lang = fence_lang(info);
+            match SYNTAXES
+                .find_syntax_by_token(lang)
+                .filter(|_| !lang.is_empty())
+            {
+                Some(syntax) => {
+                    block = Some((syntax, String::new()));
+                    vec![]
+                }
+                None => vec![event],
+            }
+        }
+        MarkEvent::Text(ref text) if block.is_some() => {
+            if let Some((_, code)) = &mut block {
+                code.push_str(text);
+            }
+            vec![]
+        }
+        MarkEvent::End(Tag::CodeBlock(CodeBlockKind::Fenced(ref info))) => match block.take() {
+            Some((syntax, code)) => match highlight(syntax, fence_lang(info), &code) {
+                Some(html) => vec![MarkEvent::Html(html.into())],
+                // Fall back to the plain block if the syntax definition fails on the code
+                None => vec![
+                    MarkEvent::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info.clone()))),
+                    MarkEvent::Text(code.into()),
+                    event,
+                ],
+            },
+            None => vec![event],
+        },
+        _ => vec![event],
+    })
+}
 
 /// Takes a number of words as long as there are words "left"
 fn take_words(left: &mut usize, cow: &str) -> usize {
@@ -35,89 +176,248 @@ fn take_words(left: &mut usize, cow: &str) -> usize {
     cow.len()
 }
 
-/// Truncates markdown text/html/code token
-/// See https://docs.rs/pulldown-cmark/latest/pulldown_cmark/enum.CowStr.html
-fn trunc_cow(cow: CowStr<'_>, len: usize, ellipses: bool) -> CowStr<'_> {
-    if cow.len() == len {
-        return cow;
-    } else if len == 0 {
-        return CowStr::Borrowed(if ellipses { "..." } else { "" });
-    }
+/// Cuts a text token after `len` bytes and marks the cut with an ellipsis
+fn trunc_cow(cow: CowStr<'_>, len: usize) -> CowStr<'_> {
+    let mut text = cow[..len].trim_end().to_owned();
+    text.push_str("...");
+    text.into()
+}
+
+fn post_options() -> MarkOption {
+    let extensions = &CONFIG.markdown;
+    let mut options = MarkOption::ENABLE_STRIKETHROUGH;
+    options.set(MarkOption::ENABLE_TABLES, extensions.tables);
+    options.set(MarkOption::ENABLE_FOOTNOTES, extensions.footnotes);
+    options.set(MarkOption::ENABLE_TASKLISTS, extensions.task_lists);
+    options.set(
+        MarkOption::ENABLE_SMART_PUNCTUATION,
+        extensions.smart_punctuation,
+    );
+    options
+}
+
+/// A heading in a post, for its anchor and the table of contents
+#[derive(Debug, Clone)]
+pub struct Heading {
+    pub level: u32,
+    pub id: String,
+    pub text: String,
+}
+
+/// Ids the post page already uses, which headings can't take
+const RESERVED_IDS: &[&str] = &[
+    "comment-body",
+    "comment-form",
+    "comment-name",
+    "comments",
+    "header-bg",
+    "header-inner",
+    "site-search",
+    "site-tagline",
+    "site-title",
+    "toc",
+    "wrapper",
+];
 
-    match cow {
-        CowStr::Borrowed(b) if !ellipses => CowStr::Borrowed(&b[0..len]),
-        CowStr::Inlined(inl) if !ellipses => CowStr::Inlined(
-            InlineStr::try_from(&inl[0..len]).expect("slice should not exceed inline boundries"),
-        ),
-        _ => {
-            let slice = &cow[0..len];
-            InlineStr::try_from(slice)
-                .map(CowStr::Inlined)
-                .ok()
-                .unwrap_or_else(|| {
-                    let mut s = String::from(slice);
-                    if ellipses {
-                        s.push_str("...");
-                    }
-                    CowStr::Boxed(s.into_boxed_str())
-                })
+/// Finds the headings in `body` and gives each an id made from its text.
+/// Repeated headings get a numeric suffix, so the ids only change when the headings do.
+pub fn headings(body: &str) -> Vec<Heading> {
+    let mut used = RESERVED_IDS
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<HashSet<_>>();
+    let mut headings = Vec::new();
+    let mut current: Option<(u32, String)> = None;
+
+    for event in Parser::new_ext(body, post_options()) {
+        match event {
+            MarkEvent::Start(Tag::Heading(level, ..)) => {
+                current = Some((level as u32, String::new()));
+            }
+            MarkEvent::Text(text) | MarkEvent::Code(text) => {
+                if let Some((_, heading)) = &mut current {
+                    heading.push_str(&text);
+                }
+            }
+            MarkEvent::End(Tag::Heading(..)) => {
+                let (level, text) = match current.take() {
+                    Some(heading) => heading,
+                    None => continue,
+                };
+                let mut base = slugify(&text, 60);
+                if base.is_empty() {
+                    base.push_str("section");
+                }
+
+                let mut id = base.clone();
+                let mut n = 1;
+                while !used.insert(id.clone()) {
+                    n += 1;
+                    id = format!("{}-{}", base, n);
+                }
+                headings.push(Heading {
+                    level,
+                    id,
+                    text: text.trim().to_owned(),
+                });
+            }
+            _ => {}
         }
     }
+
+    headings
 }
 
-fn post_options() -> MarkOption {
-    MarkOption::ENABLE_STRIKETHROUGH
+/// Gives each heading the id found for it by `headings`
+fn anchor_headings<'a>(
+    events: impl Iterator<Item = MarkEvent<'a>>,
+    headings: &'a [Heading],
+) -> impl Iterator<Item = MarkEvent<'a>> {
+    let mut headings = headings.iter();
+
+    events.map(move |event| match event {
+        MarkEvent::Start(Tag::Heading(level, ..)) => match headings.next() {
+            Some(heading) => MarkEvent::Html(format!("<{} id=\"{}\">", level, heading.id).into()),
+            None => event,
+        },
+        MarkEvent::End(Tag::Heading(level, ..)) => {
+            MarkEvent::Html(format!("</{}>\n", level).into())
+        }
+        _ => event,
+    })
+}
+
+/// Where the author wants the preview of a post to end
+const MORE_MARKER: &str = "<!--more-->";
+
+fn is_more_marker(event: &MarkEvent) -> bool {
+    matches!(event, MarkEvent::Html(html) if html.trim() == MORE_MARKER)
 }
 
-pub fn render_preview(full_body: &str, buffer: &mut String) -> bool {
-    let mut words = CONFIG.preview_size;
-    let mut level = 0;
+/// Renders the start of a post into `buffer`: everything before a `<!--more-->` marker,
+/// or else about `preview_size` words. Returns whether it was shortened.
+/// Raw html is escaped unless the post is `trusted`, and links are always cleaned.
+pub fn render_preview(
+    full_body: &str,
+    buffer: &mut String,
+    preview_size: usize,
+    trusted: bool,
+) -> bool {
+    let has_marker = Parser::new_ext(full_body, post_options()).any(|event| is_more_marker(&event));
+    let mut words = if has_marker { usize::MAX } else { preview_size };
     let mut shortened = false;
+    // Tags that have been started and not ended yet, so they can be closed wherever the cut is
+    let mut open = Vec::new();
+    let mut events = Vec::new();
 
-    let truncated = Parser::new_ext(full_body, post_options()).map_while(|event| {
-        if level == 0 && words == 0 {
+    for event in Parser::new_ext(full_body, post_options()) {
+        // Do not show any content after the marker or a line break
+        if is_more_marker(&event) || event == MarkEvent::Rule {
             shortened = true;
-            None
-        } else {
-            let ellipses = words > 0;
-            Some(match event {
-                MarkEvent::Code(text) => {
-                    let len = take_words(&mut words, &text);
-                    MarkEvent::Code(trunc_cow(text.clone(), len, ellipses))
-                }
-                MarkEvent::Text(text) | MarkEvent::Html(text) => {
-                    let len = take_words(&mut words, &text);
-                    MarkEvent::Text(trunc_cow(text.clone(), len, ellipses))
-                }
-                // Do not show any content after a line break
-                MarkEvent::Rule => {
+            break;
+        }
+        let in_link = open
+            .iter()
+            .any(|tag| matches!(tag, Tag::Link(..) | Tag::Image(..)));
+        let event = match event {
+            MarkEvent::Html(html) if !trusted => MarkEvent::Text(html),
+            _ => event,
+        };
+
+        // Out of words. Tags still get closed, but there's no more content.
+        if words == 0 && !matches!(event, MarkEvent::End(_)) {
+            shortened = true;
+            break;
+        }
+
+        let event = match event {
+            MarkEvent::Start(tag) => {
+                open.push(tag.clone());
+                MarkEvent::Start(tag)
+            }
+            MarkEvent::End(tag) => {
+                open.pop();
+                MarkEvent::End(tag)
+            }
+            // Code spans, links and images are kept whole, even if they go over
+            MarkEvent::Code(text) => {
+                take_words(&mut words, &text);
+                MarkEvent::Code(text)
+            }
+            MarkEvent::Text(text) if in_link => {
+                take_words(&mut words, &text);
+                MarkEvent::Text(text)
+            }
+            MarkEvent::Text(text) => {
+                let len = take_words(&mut words, &text);
+                if text[len..].trim().is_empty() {
+                    MarkEvent::Text(text)
+                } else {
                     shortened = true;
-                    return None;
-                }
-                MarkEvent::Start(_) => {
-                    level += 1;
-                    event
-                }
-                MarkEvent::End(_) => {
-                    level -= 1;
-                    event
+                    MarkEvent::Text(trunc_cow(text, len))
                 }
-                _ => event,
-            })
-        }
-    });
+            }
+            // Html that's kept is left whole, since the sanitizer can't fix half a tag
+            _ => event,
+        };
+        events.push(event);
+    }
 
-    push_html(buffer, truncated);
+    events.extend(open.into_iter().rev().map(MarkEvent::End));
+
+    let mut html = String::new();
+    push_html(&mut html, highlight_code(events.into_iter()));
+    let sanitizer = if trusted {
+        &SANITIZER
+    } else {
+        &STRICT_SANITIZER
+    };
+    buffer.push_str(&sanitizer.clean(&html).to_string());
     shortened
 }
 
-pub fn render_full(body: &str) -> String {
-    let events = Parser::new_ext(body, post_options()).map(|event| match event {
-        MarkEvent::Html(s) => MarkEvent::Text(s),
-        _ => event,
-    });
+/// Renders a post or comment. Headings get an id when `headings` are given.
+/// Raw html is escaped, unless the text is `trusted` and it's in the sanitizer's allow-list.
+/// Untrusted text is still cleaned, so its links and images can't run scripts.
+fn render(body: &str, headings: Option<&[Heading]>, trusted: bool) -> String {
+    let events = Parser::new_ext(body, post_options())
+        .filter(|event| !is_more_marker(event))
+        .map(|event| match event {
+            MarkEvent::Html(s) if !trusted => MarkEvent::Text(s),
+            _ => event,
+        });
+
+    let mut html = String::new();
+    match headings {
+        Some(headings) => push_html(&mut html, highlight_code(anchor_headings(events, headings))),
+        None => push_html(&mut html, highlight_code(events)),
+    }
+
+    let sanitizer = if trusted {
+        &SANITIZER
+    } else {
+        &STRICT_SANITIZER
+    };
+    sanitizer.clean(&html).to_string()
+}
+
+/// Renders a post along with its table of contents, if it has enough headings for one
+pub fn render_with_toc(body: &str, trusted: bool) -> (String, Option<Vec<Heading>>) {
+    let headings = headings(body);
+    let html = render(body, Some(&headings), trusted);
+
+    let toc = CONFIG
+        .markdown
+        .toc_min_headings
+        .filter(|min| headings.len() >= *min)
+        .map(|_| headings);
+    (html, toc)
+}
 
-    let mut body = String::new();
-    push_html(&mut body, events);
-    body
+/// Comments use the same markdown as posts, but their html is always escaped
+/// and their links are cleaned. Their headings get no ids so they can't clash with the post's.
+pub fn render_comment(body: &str) -> String {
+    STRICT_SANITIZER
+        .clean(&render(body, None, false))
+        .to_string()
 }
@@ -1,14 +1,55 @@
+mod auth;
+mod build;
+mod cache;
+mod cli;
+mod comments;
+mod conditional;
 mod config;
+mod editor;
 mod entities;
+mod export;
+mod feeds;
 mod format;
+mod media;
 mod posts;
+mod revisions;
+mod search;
+mod slug;
+mod tags;
 
-use self::posts::*;
+#[cfg(test)]
+mod tests;
+
+use self::{
+    auth::*, comments::*, editor::*, feeds::*, media::*, posts::*, revisions::*, search::*, tags::*,
+};
 use actix_files::Files;
-use actix_web::{web, App, HttpServer};
+use actix_session::{
+    config::CookieContentSecurity, storage::CookieSessionStore, SessionMiddleware,
+};
+use actix_web::{cookie::Key, http::KeepAlive, middleware::DefaultHeaders, web, App, HttpServer};
+use clap::Parser;
 use config::CONFIG;
 use sea_orm::{Database, DatabaseConnection};
-use std::env;
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// b5 blogging software
+#[derive(Parser, Debug)]
+struct Args {
+    /// JSON or TOML config file. Defaults to ./config.json, then ./config.toml.
+    /// Fields can be overridden with B5_* environment variables, such as B5_PAGE_SIZE.
+    #[clap(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Run a command instead of starting the server
+    #[clap(subcommand)]
+    command: Option<cli::Command>,
+}
 
 #[derive(Debug, Clone)]
 pub struct AppState {
@@ -17,33 +58,180 @@ pub struct AppState {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    println!("Starting {}", CONFIG.title);
+    let args = Args::parse();
     dotenv::dotenv().unwrap();
-    let connection_str = env::var("DATABASE_URL").expect("no connection string fond in env");
 
-    let db = Database::connect(&connection_str)
-        .await
-        .expect("Could not create db");
+    // Most commands only need the database
+    if let Some(command) = args.command {
+        if command.needs_config() {
+            if let Err(e) = config::init(args.config.as_deref()) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        if let Err(e) = cli::run(&connect().await, command).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let config = match config::init(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("Starting {}", config.title);
+    std::fs::create_dir_all(&config.media.dir)?;
+
+    let db = connect().await;
     println!("Connected to DB. Running server...");
 
-    HttpServer::new(move || {
+    let session_key = match env::var("SESSION_KEY") {
+        Ok(key) if key.len() < 64 => {
+            eprintln!("SESSION_KEY must be at least 64 bytes");
+            std::process::exit(1);
+        }
+        Ok(key) => Key::from(key.as_bytes()),
+        Err(_) => {
+            println!("No SESSION_KEY found in env. Sessions will not survive a restart.");
+            Key::generate()
+        }
+    };
+
+    let mut server = HttpServer::new(move || {
         App::new()
+            .wrap(session(session_key.clone()))
             .app_data(web::Data::new(AppState { db: db.clone() }))
-            .service(home)
-            .service(rss)
-            .route("/posts", web::get().to(posts_page))
-            .service(post_page)
-            .service(Files::new("/static", "./static"))
-    })
-    .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
+            .configure(routes)
+    });
+
+    let options = &config.server;
+    if let Some(workers) = options.workers {
+        server = server.workers(workers);
+    }
+    if let Some(seconds) = options.keep_alive {
+        server = server.keep_alive(match seconds {
+            0 => KeepAlive::Disabled,
+            seconds => KeepAlive::Timeout(Duration::from_secs(seconds)),
+        });
+    }
+
+    let address = (options.address.as_str(), options.port);
+    let (server, listening) = match (&options.unix_socket, &options.tls) {
+        #[cfg(unix)]
+        (Some(path), _) => {
+            // A socket left behind by the last run would stop us from binding
+            if config::is_socket(path) {
+                std::fs::remove_file(path)?;
+            }
+            (server.bind_uds(path)?, path.display().to_string())
+        }
+        (_, Some(tls)) => (
+            server.bind_rustls(address, tls_config(tls)?)?,
+            format!("https://{}:{}", address.0, address.1),
+        ),
+        _ => (
+            server.bind(address)?,
+            format!("http://{}:{}", address.0, address.1),
+        ),
+    };
+
+    println!("Listening on {}", listening);
+    server.run().await
 }
 
-/*
-Levels:
-0 - commenter
-1 - author
-2 - mod
-3 - admin
-*/
+async fn connect() -> DatabaseConnection {
+    let connection_str = env::var("DATABASE_URL").expect("no connection string fond in env");
+    Database::connect(&connection_str)
+        .await
+        .expect("Could not create db")
+}
+
+/// Reads the certificate chain and private key for HTTPS
+fn tls_config(tls: &config::Tls) -> io::Result<rustls::ServerConfig> {
+    let read = |path: &Path| -> io::Result<Vec<rustls_pemfile::Item>> {
+        let file = File::open(path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Could not read {}: {}", path.display(), e),
+            )
+        })?;
+        rustls_pemfile::read_all(&mut BufReader::new(file))
+    };
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let certs = read(&tls.cert)?
+        .into_iter()
+        .filter_map(|item| match item {
+            rustls_pemfile::Item::X509Certificate(cert) => Some(rustls::Certificate(cert)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if certs.is_empty() {
+        return Err(invalid(format!(
+            "No certificates found in {}",
+            tls.cert.display()
+        )));
+    }
+
+    let key = read(&tls.key)?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| invalid(format!("No private key found in {}", tls.key.display())))?;
+
+    rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| invalid(e.to_string()))
+}
+
+fn session(key: Key) -> SessionMiddleware<CookieSessionStore> {
+    SessionMiddleware::builder(CookieSessionStore::default(), key)
+        .cookie_content_security(CookieContentSecurity::Signed)
+        .build()
+}
+
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(home)
+        .service(rss)
+        .service(atom)
+        .service(json)
+        .route("/posts", web::get().to(posts_page))
+        .service(posts_before)
+        .service(search)
+        .service(post_page)
+        .service(tag_page)
+        .service(tag_page_before)
+        .service(tag_feed)
+        .service(add_comment)
+        .service(comment_queue)
+        .service(moderate_comments)
+        .service(login_page)
+        .service(login)
+        .service(logout)
+        .service(admin_posts)
+        .service(new_post_page)
+        .service(new_post)
+        .service(edit_post_page)
+        .service(edit_post)
+        .service(revisions_page)
+        .service(revision_diff)
+        .service(restore_revision)
+        .service(media_page)
+        .service(upload_media)
+        .service(
+            web::scope("/static")
+                .wrap(DefaultHeaders::new().add(conditional::public(CONFIG.cache.static_max_age)))
+                .service(Files::new("", "./static")),
+        )
+        .service(Files::new(MEDIA_URL, &CONFIG.media.dir));
+}
@@ -0,0 +1,248 @@
+use crate::{
+    auth::{redirect, AuthorUser, RequireRole},
+    config::CONFIG,
+    entities::{media, prelude::*, users},
+    slug::slugify,
+    AppState,
+};
+use actix_multipart::Multipart;
+use actix_web::{
+    error::{ErrorBadRequest, ErrorInternalServerError, ErrorPayloadTooLarge},
+    get,
+    http::header,
+    post, web, HttpRequest, HttpResponse, Result as ActixResult,
+};
+use futures_util::TryStreamExt;
+use image::{imageops::FilterType, ImageFormat};
+use rand::Rng;
+use sailfish::TemplateOnce;
+use sea_orm::{entity::*, query::*};
+use serde::Serialize;
+use std::path::Path;
+
+/// URL the media directory is served under
+pub const MEDIA_URL: &str = "/media";
+
+/// Column limit from the migrations
+const ORIGINAL_NAME_LEN: usize = 255;
+
+/// Extensions that can be uploaded, with the content type they're recorded as.
+/// SVGs are left out since they can carry scripts.
+const ALLOWED_TYPES: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("pdf", "application/pdf"),
+];
+
+/// What was learned from an uploaded image
+struct ImageInfo {
+    width: u32,
+    height: u32,
+    thumbnail: Option<String>,
+}
+
+/// Checks that `bytes` is an image and saves a thumbnail next to `file_name`
+/// if it's wider than `thumbnail_width`
+fn process_image(
+    dir: &Path,
+    file_name: &str,
+    format: ImageFormat,
+    bytes: &[u8],
+    thumbnail_width: u32,
+) -> Result<ImageInfo, image::ImageError> {
+    let image = image::load_from_memory_with_format(bytes, format)?;
+    let (width, height) = (image.width(), image.height());
+
+    let thumbnail = if width > thumbnail_width {
+        // Only the first frame of a GIF is kept, so its thumbnail is a PNG
+        let (thumb_format, ext) = match format {
+            ImageFormat::Jpeg => (ImageFormat::Jpeg, "jpg"),
+            _ => (ImageFormat::Png, "png"),
+        };
+        let stem = file_name
+            .rsplit_once('.')
+            .map_or(file_name, |(stem, _)| stem);
+        let thumb_name = format!("{}-thumb.{}", stem, ext);
+
+        image
+            .resize(thumbnail_width, u32::MAX, FilterType::Triangle)
+            .save_with_format(dir.join(&thumb_name), thumb_format)?;
+        Some(thumb_name)
+    } else {
+        None
+    };
+
+    Ok(ImageInfo {
+        width,
+        height,
+        thumbnail,
+    })
+}
+
+/// Markdown that shows the upload in a post. Images with a thumbnail link to the full size.
+pub fn markdown(upload: &media::Model) -> String {
+    let url = format!("{}/{}", MEDIA_URL, upload.file_name);
+    // Brackets would end the link text early
+    let text = upload.original_name.replace(['[', ']'], "");
+
+    if !upload.content_type.starts_with("image/") {
+        return format!("[{}]({})", text, url);
+    }
+    let alt = text
+        .rsplit_once('.')
+        .map_or(text.as_str(), |(stem, _)| stem);
+    match &upload.thumbnail {
+        Some(thumb) => format!("[![{}]({}/{})]({})", alt, MEDIA_URL, thumb, url),
+        None => format!("![{}]({})", alt, url),
+    }
+}
+
+/// A name for the stored file that can't clash with earlier uploads
+fn stored_name(original_name: &str, ext: &str) -> String {
+    let stem = original_name
+        .rsplit_once('.')
+        .map_or(original_name, |(stem, _)| stem);
+    let mut base = slugify(stem, 80);
+    if base.is_empty() {
+        base.push_str("upload");
+    }
+    format!("{}-{:08x}.{}", base, rand::thread_rng().gen::<u32>(), ext)
+}
+
+/// Reads the `file` field of an upload, enforcing the size limit as it goes
+async fn read_upload(mut payload: Multipart) -> ActixResult<(String, Vec<u8>)> {
+    while let Some(mut field) = payload.try_next().await? {
+        if field.name() != "file" {
+            continue;
+        }
+        let name = field
+            .content_disposition()
+            .get_filename()
+            .map(str::to_owned)
+            .unwrap_or_default();
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.try_next().await? {
+            if bytes.len() + chunk.len() > CONFIG.media.max_size {
+                return Err(ErrorPayloadTooLarge(format!(
+                    "Uploads may not be larger than {} bytes",
+                    CONFIG.media.max_size
+                )));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        return Ok((name, bytes));
+    }
+    Err(ErrorBadRequest("No file was uploaded"))
+}
+
+#[derive(Serialize)]
+struct UploadResponse {
+    url: String,
+    markdown: String,
+}
+
+/// Stores an upload. Replies with JSON for the editor's script,
+/// and sends browsers without it to the media library.
+#[post("/admin/media")]
+async fn upload_media(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    RequireRole(user): AuthorUser,
+    payload: Multipart,
+) -> ActixResult<HttpResponse> {
+    let (original_name, bytes) = read_upload(payload).await?;
+    if bytes.is_empty() {
+        return Err(ErrorBadRequest("The file is empty"));
+    }
+
+    let ext = original_name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    let content_type = ALLOWED_TYPES
+        .iter()
+        .find(|(allowed, _)| *allowed == ext)
+        .map(|(_, content_type)| *content_type)
+        .ok_or_else(|| ErrorBadRequest("That kind of file can't be uploaded"))?;
+
+    let size = u32::try_from(bytes.len()).map_err(ErrorPayloadTooLarge)?;
+    let file_name = stored_name(&original_name, &ext);
+    let format = ImageFormat::from_extension(&ext);
+
+    // Decoding and resizing images is too slow for the async workers
+    let dir = CONFIG.media.dir.clone();
+    let stored = file_name.clone();
+    let image = web::block(move || -> Result<Option<ImageInfo>, String> {
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let image = match format {
+            Some(format) => Some(
+                process_image(&dir, &stored, format, &bytes, CONFIG.media.thumbnail_width)
+                    .map_err(|_| "The image could not be read".to_owned())?,
+            ),
+            None => None,
+        };
+        std::fs::write(dir.join(&stored), &bytes).map_err(|e| e.to_string())?;
+        Ok(image)
+    })
+    .await
+    .map_err(ErrorInternalServerError)?
+    .map_err(ErrorBadRequest)?;
+
+    let upload = media::ActiveModel {
+        uploader_id: Set(Some(user.user_id)),
+        file_name: Set(file_name),
+        original_name: Set(original_name.chars().take(ORIGINAL_NAME_LEN).collect()),
+        content_type: Set(content_type.to_owned()),
+        size: Set(size),
+        width: Set(image.as_ref().map(|image| image.width)),
+        height: Set(image.as_ref().map(|image| image.height)),
+        thumbnail: Set(image.and_then(|image| image.thumbnail)),
+        uploaded_at: Set(chrono::Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .insert(&data.db)
+    .await
+    .map_err(ErrorInternalServerError)?;
+
+    let wants_json = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+    if wants_json {
+        Ok(HttpResponse::Ok().json(UploadResponse {
+            url: format!("{}/{}", MEDIA_URL, upload.file_name),
+            markdown: markdown(&upload),
+        }))
+    } else {
+        Ok(redirect("/admin/media"))
+    }
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "media.stpl")]
+struct MediaTemplate {
+    uploads: Vec<(media::Model, Option<users::Model>)>,
+}
+
+/// Lists every upload with the markdown to use it in a post
+#[get("/admin/media")]
+async fn media_page(data: web::Data<AppState>, _user: AuthorUser) -> ActixResult<HttpResponse> {
+    let uploads = Media::find()
+        .find_also_related(Users)
+        .order_by_desc(media::Column::MediaId)
+        .all(&data.db)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html")
+        .body(MediaTemplate { uploads }.render_once().unwrap()))
+}
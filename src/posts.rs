@@ -1,11 +1,20 @@
 use crate::format::*;
 use crate::{
+    auth::{CurrentUser, Role},
+    cache,
+    comments::{post_comments, CommentBox, CommentData},
+    conditional::{self, Validators},
     config::CONFIG,
     entities::{self, prelude::*},
+    slug::renamed_post,
+    tags::tags_for_post,
     AppState,
 };
-use actix_web::{error::ErrorInternalServerError, get, web, HttpResponse, Result as ActixResult};
-use entities::{blog_posts, users};
+use actix_web::{
+    error::ErrorInternalServerError, get, http::header, web, HttpRequest, HttpResponse,
+    Result as ActixResult,
+};
+use entities::{blog_posts, tags, users};
 use sailfish::TemplateOnce;
 use sea_orm::{entity::*, prelude::*, query::*, sea_query::IntoCondition, FromQueryResult};
 use serde::Deserialize;
@@ -16,14 +25,64 @@ pub struct CursorQuery {
     after: Option<u32>,
 }
 
+impl CursorQuery {
+    /// The page of posts older than `post_id`
+    pub fn before(post_id: u32) -> Self {
+        CursorQuery {
+            before: Some(post_id),
+            after: None,
+        }
+    }
+}
+
 #[derive(FromQueryResult, Debug)]
-struct PostData {
+pub struct PostData {
     pub post_id: u32,
+    pub slug: String,
     pub title: String,
     pub body: String,
     pub username: String,
+    pub author_level: users::Level,
     pub post_date: DateTime,
     pub description: Option<String>,
+    pub last_updated: Option<DateTime>,
+    pub preview_size: Option<u32>,
+}
+
+impl PostData {
+    /// When the post last changed. A scheduled post edited before it went live
+    /// changed when it went live.
+    pub fn updated(&self) -> DateTime {
+        self.last_updated
+            .map_or(self.post_date, |updated| updated.max(self.post_date))
+    }
+
+    /// Whether the author is trusted to use the html allowed by the config
+    pub fn trusted_html(&self) -> bool {
+        CONFIG
+            .html
+            .trusted_role
+            .is_some_and(|role| Role::from(self.author_level) >= role)
+    }
+}
+
+/// Posts that anyone may read: not drafts, and not scheduled for later
+pub fn published() -> Condition {
+    Condition::all()
+        .add(blog_posts::Column::Status.ne(blog_posts::PostStatus::Draft))
+        .add(blog_posts::Column::PostDate.lte(chrono::Utc::now().naive_utc()))
+}
+
+/// Posts that `user` may read. Authors can see their own unpublished posts
+/// and mods can see everything.
+pub fn readable_by(user: Option<&CurrentUser>) -> Condition {
+    match user {
+        Some(user) if user.role() >= Role::Mod => Condition::all(),
+        Some(CurrentUser(user)) => Condition::any()
+            .add(published())
+            .add(blog_posts::Column::AuthorId.eq(user.user_id)),
+        None => published(),
+    }
 }
 
 #[derive(FromQueryResult, Debug)]
@@ -31,27 +90,119 @@ struct CountData {
     count: i64,
 }
 
-struct PostPreview {
-    data: PostData,
-    read_more: bool,
+#[derive(FromQueryResult, Debug)]
+struct PostIdData {
+    post_id: u32,
+}
+
+pub struct PostPreview {
+    pub data: PostData,
+    pub read_more: bool,
+}
+
+impl From<PostData> for PostPreview {
+    fn from(mut data: PostData) -> Self {
+        let words = data
+            .preview_size
+            .map_or(CONFIG.preview_size, |size| size as usize);
+        let (body, read_more) = cache::preview(&data, words);
+        data.body = body;
+
+        PostPreview { data, read_more }
+    }
 }
 
 #[derive(TemplateOnce)]
 #[template(path = "posts.stpl")]
-struct PostsTemplate {
+pub struct PostsTemplate {
     entries: Vec<PostPreview>,
-    next: Option<u32>,
-    prev: Option<u32>,
+    next: Option<String>,
+    prev: Option<String>,
+    heading: Option<String>,
 }
 
 pub async fn posts_page(
-    //_req: HttpRequest,
+    req: HttpRequest,
     data: web::Data<AppState>,
     query_str: web::Query<CursorQuery>,
 ) -> ActixResult<HttpResponse> {
+    list_posts(
+        &req,
+        &data.db,
+        &query_str,
+        Condition::all(),
+        "/posts".into(),
+        None,
+    )
+    .await
+}
+
+#[get("/posts/before/{id}")]
+async fn posts_before(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    id: web::Path<u32>,
+) -> ActixResult<HttpResponse> {
+    list_posts(
+        &req,
+        &data.db,
+        &CursorQuery::before(*id),
+        Condition::all(),
+        "/posts".into(),
+        None,
+    )
+    .await
+}
+
+/// Renders a page of the published posts that match `filter`, or a 304 if the client has it.
+/// Pagination links point back to `page_url` and `heading` is shown above the posts.
+pub async fn list_posts(
+    req: &HttpRequest,
+    db: &DatabaseConnection,
+    query_str: &CursorQuery,
+    filter: Condition,
+    page_url: String,
+    heading: Option<String>,
+) -> ActixResult<HttpResponse> {
+    let (page, _) = load_posts(db, query_str, filter, &page_url, heading).await?;
+
+    let last_modified = page.entries.iter().map(|entry| entry.data.updated()).max();
+    let version = (
+        page.entries
+            .iter()
+            .map(|entry| (entry.data.post_id, &entry.data.body))
+            .collect::<Vec<_>>(),
+        &page.next,
+        &page.prev,
+    );
+    let validators = Validators::new(last_modified, version).vary_cookie();
+    Ok(validators.respond(
+        req,
+        conditional::public(CONFIG.cache.html_max_age),
+        "text/html",
+        || page.render_once().unwrap(),
+    ))
+}
+
+/// Loads a page of the published posts that match `filter`, along with the
+/// cursor for the page of older posts if there is one.
+///
+/// Pages are linked with path based urls like `{page_url}/before/{id}`, so they can
+/// be written out as files. Each page of posts gets a single url: the link to newer
+/// posts points to the page those posts are on when paging back from the first one.
+pub async fn load_posts(
+    db: &DatabaseConnection,
+    query_str: &CursorQuery,
+    filter: Condition,
+    page_url: &str,
+    heading: Option<String>,
+) -> ActixResult<(PostsTemplate, Option<u32>)> {
+    let filter = filter.add(published());
     let mut query = BlogPosts::find()
         .column(users::Column::Username)
+        .column_as(users::Column::Level, "author_level")
         .join(JoinType::InnerJoin, blog_posts::Relation::Users.def())
+        .filter(filter.clone())
         .limit(CONFIG.page_size);
 
     let mut reverse = false;
@@ -68,7 +219,7 @@ pub async fn posts_page(
         query
     }
     .into_model::<PostData>()
-    .all(&data.db)
+    .all(db)
     .await
     .map_err(ErrorInternalServerError)?;
 
@@ -78,16 +229,7 @@ pub async fn posts_page(
 
     let entries = entries
         .into_iter()
-        .map(|mut entry| {
-            let mut body = String::new();
-            let read_more = render_preview(&entry.body, &mut body);
-            entry.body = body;
-
-            PostPreview {
-                data: entry,
-                read_more,
-            }
-        })
+        .map(PostPreview::from)
         .collect::<Vec<_>>();
 
     let mut next = None;
@@ -95,27 +237,41 @@ pub async fn posts_page(
 
     // Check if any previous posts exist
     if let Some(post) = entries.last() {
-        let cond = blog_posts::Column::PostId.lt(post.data.post_id);
-        let has_prev = any_posts_where(&data.db, cond).await?;
+        let cond = filter
+            .clone()
+            .add(blog_posts::Column::PostId.lt(post.data.post_id));
+        let has_prev = any_posts_where(db, cond).await?;
         prev = Some(post.data.post_id).filter(|_| has_prev);
     }
 
-    // Check if any later posts exist
+    // Find the page the later posts are on. A page full of them and
+    // one more post means the page starts before that post.
     if let Some(post) = entries.first() {
-        let cond = blog_posts::Column::PostId.gt(post.data.post_id);
-        let has_next = any_posts_where(&data.db, cond).await?;
-        next = Some(post.data.post_id).filter(|_| has_next);
+        let newer = BlogPosts::find()
+            .select_only()
+            .column(blog_posts::Column::PostId)
+            .filter(filter.add(blog_posts::Column::PostId.gt(post.data.post_id)))
+            .order_by_asc(blog_posts::Column::PostId)
+            .limit(CONFIG.page_size + 1)
+            .into_model::<PostIdData>()
+            .all(db)
+            .await
+            .map_err(ErrorInternalServerError)?;
+
+        next = match newer.get(CONFIG.page_size as usize) {
+            Some(data) => Some(format!("{}/before/{}", page_url, data.post_id)),
+            None if !newer.is_empty() => Some(page_url.to_owned()),
+            None => None,
+        };
     }
 
-    Ok(HttpResponse::Ok().content_type("text/html").body(
-        PostsTemplate {
-            entries,
-            next,
-            prev,
-        }
-        .render_once()
-        .unwrap(),
-    ))
+    let page = PostsTemplate {
+        entries,
+        next,
+        prev: prev.map(|id| format!("{}/before/{}", page_url, id)),
+        heading,
+    };
+    Ok((page, prev))
 }
 
 async fn any_posts_where(db: &DatabaseConnection, filter: impl IntoCondition) -> ActixResult<bool> {
@@ -132,27 +288,99 @@ async fn any_posts_where(db: &DatabaseConnection, filter: impl IntoCondition) ->
 
 #[derive(TemplateOnce)]
 #[template(path = "post.stpl")]
-struct PostTemplate {
+pub struct PostTemplate {
     post: PostData,
+    toc: Option<Vec<Heading>>,
+    tags: Vec<tags::Model>,
+    comments: Vec<CommentData>,
+    /// Left out of pages written by the static build, which can't take comments
+    comment_form: bool,
+    commenter: Option<String>,
+    comment_box: CommentBox,
 }
 
-#[get("/post/{id}")]
-pub async fn post_page(data: web::Data<AppState>, id: web::Path<u32>) -> ActixResult<HttpResponse> {
+impl PostTemplate {
+    /// Renders the body of `post` and loads what's shown around it
+    pub async fn load(
+        db: &DatabaseConnection,
+        mut post: PostData,
+        comment_form: bool,
+        commenter: Option<String>,
+        comment_box: CommentBox,
+    ) -> ActixResult<Self> {
+        let (body, toc) = cache::full(&post);
+        post.body = body;
+        let tags = tags_for_post(db, post.post_id)
+            .await
+            .map_err(ErrorInternalServerError)?;
+        let comments = post_comments(db, post.post_id).await?;
+
+        Ok(PostTemplate {
+            post,
+            toc,
+            tags,
+            comments,
+            comment_form,
+            commenter,
+            comment_box,
+        })
+    }
+}
+
+/// Renders the post matching `filter` along with its comments, or a 304 if the client has it.
+/// A rejected comment is passed back in so the form can be shown with the error.
+pub async fn render_post(
+    req: &HttpRequest,
+    db: &DatabaseConnection,
+    filter: impl IntoCondition,
+    user: Option<&CurrentUser>,
+    comment_box: CommentBox,
+) -> ActixResult<HttpResponse> {
     let post_op = BlogPosts::find()
         .column(users::Column::Username)
+        .column_as(users::Column::Level, "author_level")
         .join(JoinType::InnerJoin, blog_posts::Relation::Users.def())
-        .filter(blog_posts::Column::PostId.eq(*id))
+        .filter(filter)
+        .filter(readable_by(user))
         .into_model::<PostData>()
-        .one(&data.db)
+        .one(db)
         .await
         .map_err(ErrorInternalServerError)?;
 
-    Ok(if let Some(mut post) = post_op {
-        post.body = render_full(&post.body);
+    Ok(if let Some(post) = post_op {
+        let rejected = comment_box.error.is_some();
+        let commenter = user.map(|CurrentUser(user)| user.username.clone());
+        let page = PostTemplate::load(db, post, true, commenter, comment_box).await?;
 
-        HttpResponse::Ok()
-            .content_type("text/html")
-            .body(PostTemplate { post }.render_once().unwrap())
+        if rejected {
+            return Ok(HttpResponse::BadRequest()
+                .content_type("text/html")
+                .body(page.render_once().unwrap()));
+        }
+
+        // No Last-Modified, since approving or deleting a comment doesn't change any date
+        let version = (
+            page.post.post_id,
+            page.post.updated(),
+            &page.post.body,
+            page.comments
+                .iter()
+                .map(|comment| comment.comment_id)
+                .collect::<Vec<_>>(),
+            &page.commenter,
+            page.comment_box.pending,
+        );
+        // Signed in users see their name and their unpublished posts
+        let cache_control = match user {
+            Some(_) => conditional::private(),
+            None => conditional::public(CONFIG.cache.html_max_age),
+        };
+        Validators::new(None, version).vary_cookie().respond(
+            req,
+            cache_control,
+            "text/html",
+            || page.render_once().unwrap(),
+        )
     } else {
         HttpResponse::NotFound()
             .content_type("text/html")
@@ -160,36 +388,69 @@ pub async fn post_page(data: web::Data<AppState>, id: web::Path<u32>) -> ActixRe
     })
 }
 
-#[derive(TemplateOnce)]
-#[template(path = "rss.stpl")]
-struct RssTemplate {
-    posts: Vec<PostData>,
+#[derive(Debug, Deserialize, Default)]
+pub struct PostQuery {
+    #[serde(default)]
+    pending: bool,
 }
 
-#[get("/feed.rss")]
-async fn rss(data: web::Data<AppState>) -> ActixResult<HttpResponse> {
-    let posts = BlogPosts::find()
-        .order_by_desc(blog_posts::Column::PostId)
-        .limit(CONFIG.rss_size)
-        .column(users::Column::Username)
-        .join(JoinType::InnerJoin, blog_posts::Relation::Users.def())
-        .into_model::<PostData>()
-        .all(&data.db)
+#[get("/post/{slug}")]
+pub async fn post_page(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    user: Option<CurrentUser>,
+    slug: web::Path<String>,
+    query: web::Query<PostQuery>,
+) -> ActixResult<HttpResponse> {
+    // Posts used to be addressed by id. Slugs are never all digits.
+    if let Ok(id) = slug.parse::<u32>() {
+        let post = BlogPosts::find_by_id(id)
+            .filter(readable_by(user.as_ref()))
+            .one(&data.db)
+            .await
+            .map_err(ErrorInternalServerError)?;
+
+        if let Some(post) = post {
+            return Ok(HttpResponse::MovedPermanently()
+                .insert_header((header::LOCATION, format!("/post/{}", post.slug)))
+                .finish());
+        }
+    }
+
+    // Slugs that were changed after publication point to the post's new one
+    let live = BlogPosts::find()
+        .filter(blog_posts::Column::Slug.eq(slug.as_str()))
+        .count(&data.db)
         .await
-        .map_err(ErrorInternalServerError)?
-        .into_iter()
-        .map(|mut post| {
-            post.body = render_full(&post.body);
-            post
-        })
-        .collect();
+        .map_err(ErrorInternalServerError)?;
+    if live == 0 {
+        let renamed = match renamed_post(&data.db, &slug)
+            .await
+            .map_err(ErrorInternalServerError)?
+        {
+            Some(id) => BlogPosts::find_by_id(id)
+                .filter(readable_by(user.as_ref()))
+                .one(&data.db)
+                .await
+                .map_err(ErrorInternalServerError)?,
+            None => None,
+        };
+        if let Some(post) = renamed {
+            return Ok(HttpResponse::MovedPermanently()
+                .insert_header((header::LOCATION, format!("/post/{}", post.slug)))
+                .finish());
+        }
+    }
 
-    Ok(HttpResponse::Ok()
-        .content_type("application/rss+xml")
-        .body(RssTemplate { posts }.render_once().unwrap()))
+    let comment_box = CommentBox {
+        pending: query.pending,
+        ..Default::default()
+    };
+    let filter = blog_posts::Column::Slug.eq(slug.as_str());
+    render_post(&req, &data.db, filter, user.as_ref(), comment_box).await
 }
 
 #[get("/")]
-async fn home(data: web::Data<AppState>) -> ActixResult<HttpResponse> {
-    posts_page(data, web::Query(CursorQuery::default())).await
+async fn home(req: HttpRequest, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    posts_page(req, data, web::Query(CursorQuery::default())).await
 }
@@ -0,0 +1,192 @@
+use crate::{
+    auth::{redirect, AuthorUser, RequireRole},
+    cache,
+    editor::find_editable,
+    entities::{blog_posts, post_revisions, prelude::*, users},
+    AppState,
+};
+use actix_web::{
+    error::{ErrorInternalServerError, ErrorNotFound},
+    get, post, web, HttpResponse, Result as ActixResult,
+};
+use sailfish::TemplateOnce;
+use sea_orm::{entity::*, prelude::*, query::*, FromQueryResult};
+use serde::Deserialize;
+use similar::{ChangeTag, TextDiff};
+
+/// Snapshots the current content of a post
+pub async fn save_revision<C: ConnectionTrait>(
+    db: &C,
+    post: &blog_posts::Model,
+    editor_id: u32,
+) -> Result<post_revisions::Model, DbErr> {
+    post_revisions::ActiveModel {
+        post_id: Set(post.post_id),
+        editor_id: Set(Some(editor_id)),
+        title: Set(post.title.clone()),
+        description: Set(post.description.clone()),
+        body: Set(post.body.clone()),
+        saved_at: Set(chrono::Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+}
+
+async fn find_revision(
+    db: &DatabaseConnection,
+    post_id: u32,
+    revision_id: u32,
+) -> ActixResult<post_revisions::Model> {
+    PostRevisions::find_by_id(revision_id)
+        .filter(post_revisions::Column::PostId.eq(post_id))
+        .one(db)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("not found"))
+}
+
+#[derive(FromQueryResult, Debug)]
+struct RevisionData {
+    revision_id: u32,
+    title: String,
+    saved_at: DateTime,
+    username: Option<String>,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "revisions.stpl")]
+struct RevisionsTemplate {
+    post: blog_posts::Model,
+    revisions: Vec<RevisionData>,
+}
+
+#[get("/admin/posts/{id}/revisions")]
+async fn revisions_page(
+    data: web::Data<AppState>,
+    RequireRole(user): AuthorUser,
+    id: web::Path<u32>,
+) -> ActixResult<HttpResponse> {
+    let post = find_editable(&data.db, &user, *id).await?;
+
+    let revisions = PostRevisions::find()
+        .select_only()
+        .column(post_revisions::Column::RevisionId)
+        .column(post_revisions::Column::Title)
+        .column(post_revisions::Column::SavedAt)
+        .column(users::Column::Username)
+        .join(JoinType::LeftJoin, post_revisions::Relation::Users.def())
+        .filter(post_revisions::Column::PostId.eq(post.post_id))
+        .order_by_desc(post_revisions::Column::RevisionId)
+        .into_model::<RevisionData>()
+        .all(&data.db)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html")
+        .body(RevisionsTemplate { post, revisions }.render_once().unwrap()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    from: u32,
+    to: u32,
+}
+
+struct DiffLine {
+    /// Used as the css class of the line
+    kind: &'static str,
+    text: String,
+}
+
+/// The text that gets compared between revisions
+fn revision_text(revision: &post_revisions::Model) -> String {
+    format!(
+        "Title: {}\nDescription: {}\n\n{}",
+        revision.title,
+        revision.description.as_deref().unwrap_or_default(),
+        revision.body
+    )
+}
+
+fn diff_lines(from: &post_revisions::Model, to: &post_revisions::Model) -> Vec<DiffLine> {
+    let (from, to) = (revision_text(from), revision_text(to));
+
+    TextDiff::from_lines(&from, &to)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            kind: match change.tag() {
+                ChangeTag::Delete => "delete",
+                ChangeTag::Insert => "insert",
+                ChangeTag::Equal => "equal",
+            },
+            text: change.value().trim_end_matches(['\r', '\n']).to_owned(),
+        })
+        .collect()
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "diff.stpl")]
+struct DiffTemplate {
+    post: blog_posts::Model,
+    from: post_revisions::Model,
+    to: post_revisions::Model,
+    lines: Vec<DiffLine>,
+}
+
+#[get("/admin/posts/{id}/revisions/diff")]
+async fn revision_diff(
+    data: web::Data<AppState>,
+    RequireRole(user): AuthorUser,
+    id: web::Path<u32>,
+    query: web::Query<DiffQuery>,
+) -> ActixResult<HttpResponse> {
+    let post = find_editable(&data.db, &user, *id).await?;
+    let from = find_revision(&data.db, post.post_id, query.from).await?;
+    let to = find_revision(&data.db, post.post_id, query.to).await?;
+    let lines = diff_lines(&from, &to);
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(
+        DiffTemplate {
+            post,
+            from,
+            to,
+            lines,
+        }
+        .render_once()
+        .unwrap(),
+    ))
+}
+
+/// Puts an old revision's content back on the post.
+/// This is saved as a new revision so the restore itself can be undone.
+#[post("/admin/posts/{id}/revisions/{revision}/restore")]
+async fn restore_revision(
+    data: web::Data<AppState>,
+    RequireRole(user): AuthorUser,
+    path: web::Path<(u32, u32)>,
+) -> ActixResult<HttpResponse> {
+    let (id, revision_id) = path.into_inner();
+    let post = find_editable(&data.db, &user, id).await?;
+    let revision = find_revision(&data.db, post.post_id, revision_id).await?;
+
+    let mut post: blog_posts::ActiveModel = post.into();
+    post.title = Set(revision.title);
+    post.description = Set(revision.description);
+    post.body = Set(revision.body);
+    post.last_updated = Set(Some(chrono::Utc::now().naive_utc()));
+
+    let txn = data.db.begin().await.map_err(ErrorInternalServerError)?;
+    let post = post.update(&txn).await.map_err(ErrorInternalServerError)?;
+    save_revision(&txn, &post, user.user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    txn.commit().await.map_err(ErrorInternalServerError)?;
+    cache::invalidate(post.post_id);
+
+    Ok(redirect(&format!(
+        "/admin/posts/{}/revisions",
+        post.post_id
+    )))
+}
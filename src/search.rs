@@ -0,0 +1,154 @@
+use crate::{
+    config::CONFIG,
+    entities::{blog_posts, prelude::*, users},
+    posts::{published, PostData, PostPreview},
+    AppState,
+};
+use actix_web::{
+    error::{ErrorBadRequest, ErrorInternalServerError},
+    get, web, HttpResponse, Result as ActixResult,
+};
+use sailfish::TemplateOnce;
+use sea_orm::{
+    prelude::*,
+    query::*,
+    sea_query::{Expr, LikeExpr, SimpleExpr},
+    DbBackend,
+};
+use serde::Deserialize;
+
+/// Deeper pages are refused, since every one of them makes the database skip all the earlier results
+const MAX_PAGE: u64 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+    /// Pages start at 1
+    page: Option<u64>,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "search.stpl")]
+struct SearchTemplate {
+    terms: String,
+    entries: Vec<PostPreview>,
+    next: Option<String>,
+    prev: Option<String>,
+}
+
+fn page_url(terms: &str, page: u64) -> String {
+    let query = serde_urlencoded::to_string([("q", terms), ("page", &page.to_string())])
+        .expect("search terms should always encode");
+    format!("/search?{}", query)
+}
+
+/// Postgres' version of the search index expression from the migrations
+const PG_DOCUMENT: &str = r#"to_tsvector('simple', "blog_posts"."title" || ' ' || coalesce("blog_posts"."description", '') || ' ' || "blog_posts"."body")"#;
+
+/// Posts that match the search terms.
+/// MySQL and Postgres use the search index from the migrations.
+/// SQLite has no such index, so posts have to contain every word of the terms.
+fn matches(backend: DbBackend, terms: &str) -> Condition {
+    match backend {
+        DbBackend::MySql => Condition::all().add(relevance(backend, terms).unwrap()),
+        DbBackend::Postgres => Condition::all().add(Expr::cust_with_values(
+            &format!("{} @@ plainto_tsquery('simple', $1)", PG_DOCUMENT),
+            vec![terms],
+        )),
+        DbBackend::Sqlite => terms
+            .split_whitespace()
+            .fold(Condition::all(), |cond, word| {
+                // Wildcards in the terms are searched for like any other character
+                let word = word
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                let contains = |column| {
+                    Expr::tbl(BlogPosts, column)
+                        .like(LikeExpr::new(format!("%{}%", word)).escape('\\'))
+                };
+                cond.add(
+                    Condition::any()
+                        .add(contains(blog_posts::Column::Title))
+                        .add(contains(blog_posts::Column::Description))
+                        .add(contains(blog_posts::Column::Body)),
+                )
+            }),
+    }
+}
+
+/// Relevance of a post to the search terms, if the backend can rank results
+fn relevance(backend: DbBackend, terms: &str) -> Option<SimpleExpr> {
+    match backend {
+        DbBackend::MySql => Some(Expr::cust_with_values(
+            "MATCH (`blog_posts`.`title`, `blog_posts`.`description`, `blog_posts`.`body`) AGAINST (?)",
+            vec![terms],
+        )),
+        DbBackend::Postgres => Some(Expr::cust_with_values(
+            &format!("ts_rank({}, plainto_tsquery('simple', $1))", PG_DOCUMENT),
+            vec![terms],
+        )),
+        DbBackend::Sqlite => None,
+    }
+}
+
+#[get("/search")]
+async fn search(
+    data: web::Data<AppState>,
+    query_str: web::Query<SearchQuery>,
+) -> ActixResult<HttpResponse> {
+    let terms = query_str.q.trim().to_owned();
+    let page = query_str.page.unwrap_or(1).max(1);
+    if page > MAX_PAGE {
+        return Err(ErrorBadRequest("page out of range"));
+    }
+    let offset = (page - 1)
+        .checked_mul(CONFIG.page_size)
+        .ok_or_else(|| ErrorBadRequest("page out of range"))?;
+    let mut entries = Vec::new();
+
+    if !terms.is_empty() {
+        let backend = data.db.get_database_backend();
+        let mut query = BlogPosts::find()
+            .column(users::Column::Username)
+            .column_as(users::Column::Level, "author_level")
+            .join(JoinType::InnerJoin, blog_posts::Relation::Users.def())
+            .filter(published())
+            .filter(matches(backend, &terms));
+        if let Some(relevance) = relevance(backend, &terms) {
+            query = query.order_by(relevance, Order::Desc);
+        }
+
+        // Fetch one extra result to find out if there is another page
+        entries = query
+            .order_by_desc(blog_posts::Column::PostId)
+            .offset(offset)
+            .limit(CONFIG.page_size + 1)
+            .into_model::<PostData>()
+            .all(&data.db)
+            .await
+            .map_err(ErrorInternalServerError)?;
+    }
+
+    let has_next = entries.len() as u64 > CONFIG.page_size;
+    entries.truncate(CONFIG.page_size as usize);
+
+    let next = page
+        .checked_add(1)
+        .filter(|next| has_next && *next <= MAX_PAGE)
+        .map(|next| page_url(&terms, next));
+    let prev = Some(page_url(&terms, page - 1)).filter(|_| page > 1);
+    let entries = entries.into_iter().map(PostPreview::from).collect();
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(
+        SearchTemplate {
+            terms,
+            entries,
+            next,
+            prev,
+        }
+        .render_once()
+        .unwrap(),
+    ))
+}
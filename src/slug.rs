@@ -0,0 +1,102 @@
+use crate::entities::{blog_posts, old_slugs, prelude::*};
+use sea_orm::{entity::*, prelude::*, ConnectionTrait};
+
+/// Lowercases `text` and joins its alphanumeric runs with dashes,
+/// keeping at most `max_len` characters
+pub fn slugify(text: &str, max_len: usize) -> String {
+    let mut slug = String::new();
+    let words = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty());
+
+    for word in words {
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.extend(word.chars().flat_map(char::to_lowercase));
+    }
+
+    match slug.char_indices().nth(max_len) {
+        Some((ind, _)) => slug[..ind].trim_end_matches('-').to_owned(),
+        None => slug,
+    }
+}
+
+/// Column limit from the migrations, minus room for a numeric suffix
+pub const POST_SLUG_LEN: usize = 100;
+
+/// Builds a slug for a post from `text` that no other post is using.
+/// Taken slugs get a numeric suffix, and `post_id` is the post being edited, if any.
+pub async fn unique_post_slug(
+    db: &DatabaseConnection,
+    text: &str,
+    post_id: Option<u32>,
+) -> Result<String, DbErr> {
+    let mut base = slugify(text, POST_SLUG_LEN);
+
+    // Numeric slugs would be mistaken for the old /post/{id} urls
+    if base.is_empty() {
+        base.push_str("post");
+    } else if base.chars().all(|c| c.is_ascii_digit()) {
+        base.insert_str(0, "post-");
+    }
+
+    let mut slug = base.clone();
+    for n in 2.. {
+        let mut taken = BlogPosts::find().filter(blog_posts::Column::Slug.eq(slug.as_str()));
+        if let Some(id) = post_id {
+            taken = taken.filter(blog_posts::Column::PostId.ne(id));
+        }
+
+        if taken.one(db).await?.is_none() {
+            break;
+        }
+        slug = format!("{}-{}", base, n);
+    }
+
+    Ok(slug)
+}
+
+/// Remembers that `old` used to be the slug of `post_id`, so links to it can be redirected.
+/// A post that takes back one of its old slugs stops redirecting it.
+pub async fn record_old_slug<C: ConnectionTrait>(
+    db: &C,
+    old: &str,
+    new: &str,
+    post_id: u32,
+) -> Result<(), DbErr> {
+    OldSlugs::delete_many()
+        .filter(old_slugs::Column::Slug.is_in([old, new]))
+        .exec(db)
+        .await?;
+
+    old_slugs::ActiveModel {
+        slug: Set(old.to_owned()),
+        post_id: Set(post_id),
+    }
+    .insert(db)
+    .await
+    .map(|_| ())
+}
+
+/// The post that used to have `slug`, if any
+pub async fn renamed_post(db: &DatabaseConnection, slug: &str) -> Result<Option<u32>, DbErr> {
+    Ok(OldSlugs::find_by_id(slug.to_owned())
+        .one(db)
+        .await?
+        .map(|old| old.post_id))
+}
+
+/// Whether saving a post failed because another post took its slug first.
+/// `unique_post_slug` checks before the save, so two saves at once can still clash.
+pub fn is_slug_clash(err: &DbErr) -> bool {
+    let message = err.to_string();
+    // SQLite, MySQL and Postgres word it differently
+    [
+        "UNIQUE constraint failed",
+        "Duplicate entry",
+        "duplicate key value",
+    ]
+    .iter()
+    .any(|clash| message.contains(clash))
+}
@@ -0,0 +1,187 @@
+use crate::{
+    entities::{blog_posts, post_tags, prelude::*, tags},
+    feeds::{feed, FeedFormat},
+    posts::{list_posts, CursorQuery},
+    slug::slugify,
+    AppState,
+};
+use actix_web::{
+    error::ErrorInternalServerError, get, web, HttpRequest, HttpResponse, Result as ActixResult,
+};
+use sea_orm::{entity::*, prelude::*, query::*, sea_query::Query};
+
+/// Column limit from the migrations
+const TAG_LEN: usize = 60;
+
+/// Splits a comma separated list of tag names into `(slug, name)` pairs
+fn parse_tags(names: &str) -> Vec<(String, String)> {
+    let mut tags: Vec<(String, String)> = Vec::new();
+
+    for name in names.split(',').map(str::trim) {
+        let name: String = name.chars().take(TAG_LEN).collect();
+        let slug = slugify(&name, TAG_LEN);
+
+        if !slug.is_empty() && !tags.iter().any(|(s, _)| *s == slug) {
+            tags.push((slug, name));
+        }
+    }
+
+    tags
+}
+
+/// Tags on a post, sorted by name
+pub async fn tags_for_post(
+    db: &DatabaseConnection,
+    post_id: u32,
+) -> Result<Vec<tags::Model>, DbErr> {
+    Tags::find()
+        .join(JoinType::InnerJoin, tags::Relation::PostTags.def())
+        .filter(post_tags::Column::PostId.eq(post_id))
+        .order_by_asc(tags::Column::Name)
+        .all(db)
+        .await
+}
+
+/// Replaces the tags on a post, creating any tags that don't exist yet.
+/// Run it in the transaction that saves the post, so a failure can't leave the post without tags.
+pub async fn set_post_tags<C: ConnectionTrait>(
+    db: &C,
+    post_id: u32,
+    names: &str,
+) -> Result<(), DbErr> {
+    let mut tag_ids = Vec::new();
+
+    for (slug, name) in parse_tags(names) {
+        let existing = Tags::find()
+            .filter(tags::Column::Slug.eq(slug.as_str()))
+            .one(db)
+            .await?;
+
+        let tag = match existing {
+            Some(tag) => tag,
+            None => {
+                tags::ActiveModel {
+                    slug: Set(slug),
+                    name: Set(name),
+                    ..Default::default()
+                }
+                .insert(db)
+                .await?
+            }
+        };
+        tag_ids.push(tag.tag_id);
+    }
+
+    PostTags::delete_many()
+        .filter(post_tags::Column::PostId.eq(post_id))
+        .exec(db)
+        .await?;
+
+    if !tag_ids.is_empty() {
+        PostTags::insert_many(tag_ids.into_iter().map(|tag_id| post_tags::ActiveModel {
+            post_id: Set(post_id),
+            tag_id: Set(tag_id),
+        }))
+        .exec(db)
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn find_tag(db: &DatabaseConnection, slug: &str) -> ActixResult<Option<tags::Model>> {
+    Tags::find()
+        .filter(tags::Column::Slug.eq(slug))
+        .one(db)
+        .await
+        .map_err(ErrorInternalServerError)
+}
+
+/// Limits a post query to posts with the given tag
+pub fn tagged(tag: &tags::Model) -> Condition {
+    let posts = Query::select()
+        .column(post_tags::Column::PostId)
+        .from(PostTags)
+        .and_where(post_tags::Column::TagId.eq(tag.tag_id))
+        .to_owned();
+
+    Condition::all().add(blog_posts::Column::PostId.in_subquery(posts))
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound()
+        .content_type("text/html")
+        .body("not found")
+}
+
+async fn list_tagged(
+    req: &HttpRequest,
+    db: &DatabaseConnection,
+    slug: &str,
+    query_str: &CursorQuery,
+) -> ActixResult<HttpResponse> {
+    Ok(match find_tag(db, slug).await? {
+        Some(tag) => {
+            list_posts(
+                req,
+                db,
+                query_str,
+                tagged(&tag),
+                format!("/tag/{}", tag.slug),
+                Some(format!("Tagged {}", tag.name)),
+            )
+            .await?
+        }
+        None => not_found(),
+    })
+}
+
+#[get("/tag/{slug}")]
+async fn tag_page(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    slug: web::Path<String>,
+    query_str: web::Query<CursorQuery>,
+) -> ActixResult<HttpResponse> {
+    list_tagged(&req, &data.db, &slug, &query_str).await
+}
+
+#[get("/tag/{slug}/before/{id}")]
+async fn tag_page_before(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<(String, u32)>,
+) -> ActixResult<HttpResponse> {
+    let (slug, id) = path.into_inner();
+    list_tagged(&req, &data.db, &slug, &CursorQuery::before(id)).await
+}
+
+#[get("/tag/{slug}/feed.{format}")]
+async fn tag_feed(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> ActixResult<HttpResponse> {
+    let (slug, format) = path.into_inner();
+    let format = match format.as_str() {
+        "rss" => FeedFormat::Rss,
+        "atom" => FeedFormat::Atom,
+        "json" => FeedFormat::Json,
+        _ => return Ok(not_found()),
+    };
+
+    Ok(match find_tag(&data.db, &slug).await? {
+        Some(tag) => {
+            feed(
+                &req,
+                &data.db,
+                tagged(&tag),
+                Some(tag.name),
+                format!("tag/{}", tag.slug),
+                format,
+            )
+            .await?
+        }
+        None => not_found(),
+    })
+}
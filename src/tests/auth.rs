@@ -0,0 +1,62 @@
+use super::*;
+
+#[actix_web::test]
+async fn logging_out_takes_a_post() {
+    let db = setup().await;
+    seed_user(&db, "jane", Role::Author).await;
+    let cookie = login(&db, "jane").await;
+
+    let res = call(
+        &db,
+        TestRequest::get().uri("/logout").cookie(cookie.clone()),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri("/admin/posts")
+            .cookie(cookie.clone()),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = call(&db, TestRequest::post().uri("/logout").cookie(cookie)).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    let cleared = res.response().cookies().next().unwrap().into_owned();
+    let res = call(&db, TestRequest::get().uri("/admin/posts").cookie(cleared)).await;
+    assert_ne!(res.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn commenters_can_log_out_from_posts() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    seed_user(&db, "reader", Role::Commenter).await;
+    seed_post(&db, &jane, 1, blog_posts::PostStatus::Published).await;
+    let cookie = login(&db, "reader").await;
+
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri("/post/post-01")
+            .cookie(cookie.clone()),
+    )
+    .await;
+    let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert_in_order(
+        &html,
+        &[
+            "Commenting as reader",
+            "form=\"logout-form\">Log out</button>",
+            "<form id=\"logout-form\" method=\"post\" action=\"/logout\">",
+        ],
+    );
+
+    let res = call(&db, TestRequest::post().uri("/logout").cookie(cookie)).await;
+    let cleared = res.response().cookies().next().unwrap().into_owned();
+    let res = call(&db, TestRequest::get().uri("/post/post-01").cookie(cleared)).await;
+    let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(html.contains("id=\"comment-name\""));
+    assert!(!html.contains("Log out"));
+}
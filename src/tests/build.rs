@@ -0,0 +1,137 @@
+use super::*;
+use crate::{
+    build::{build_site, BuildSummary},
+    tags::set_post_tags,
+};
+use std::{fs, path::Path};
+
+/// Links in `html` to listings and posts
+fn page_links(html: &str) -> Vec<&str> {
+    html.split("href=\"/")
+        .skip(1)
+        .filter_map(|rest| rest.split('"').next())
+        .filter(|link| link.starts_with("post"))
+        .collect()
+}
+
+/// Every html file under `dir`
+fn html_files(dir: &Path) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(html_files(&path));
+        } else if path.extension().is_some_and(|ext| ext == "html") {
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// Site relative `href` and `src` targets in `html`, without queries or fragments
+fn internal_links(html: &str) -> Vec<&str> {
+    html.split(" href=\"/")
+        .skip(1)
+        .chain(html.split(" src=\"/").skip(1))
+        .filter_map(|rest| rest.split(['"', '?', '#']).next())
+        .collect()
+}
+
+#[actix_web::test]
+async fn builds_every_page_as_files() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 12).await;
+    seed_post(&db, &author, 13, blog_posts::PostStatus::Draft).await;
+
+    let out = std::env::temp_dir().join(format!("b5-build-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&out);
+    assert_eq!(
+        build_site(&db, &out).await,
+        Ok(BuildSummary {
+            pages: 3,
+            posts: 12
+        })
+    );
+
+    let read = |path: &str| fs::read_to_string(out.join(path)).unwrap();
+    let home = read("index.html");
+    assert_eq!(home, read("posts/index.html"));
+    assert_in_order(&home, &["Post 12", "Post 08"]);
+    assert_in_order(&read("posts/before/8/index.html"), &["Post 07", "Post 03"]);
+    assert_in_order(&read("posts/before/3/index.html"), &["Post 02", "Post 01"]);
+    assert!(!out.join("posts/before/1").exists());
+
+    let post = read("post/post-01/index.html");
+    assert!(post.contains("Body of <strong>post 1</strong>"));
+    assert!(!post.contains("comment-form"));
+    assert!(!out.join("post/post-13").exists());
+
+    assert!(read("feed.rss").contains("Post 12"));
+    assert!(read("feed.atom").contains("Post 12"));
+    assert!(read("feed.json").contains("Post 12"));
+    assert_eq!(
+        read("static/main.css"),
+        fs::read_to_string("static/main.css").unwrap()
+    );
+
+    // Paging links all lead to written pages
+    for page in [
+        "index.html",
+        "posts/before/8/index.html",
+        "posts/before/3/index.html",
+    ] {
+        for link in page_links(&read(page)) {
+            assert!(
+                out.join(link).join("index.html").exists(),
+                "{} links to missing /{}",
+                page,
+                link
+            );
+        }
+    }
+}
+
+#[actix_web::test]
+async fn builds_tag_pages_and_every_link_has_a_file() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 7).await;
+    for id in 1..=6 {
+        set_post_tags(&db, id, "Rust").await.unwrap();
+    }
+    set_post_tags(&db, 7, "Web Dev").await.unwrap();
+
+    let out = std::env::temp_dir().join(format!("b5-build-tags-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&out);
+    assert_eq!(
+        build_site(&db, &out).await,
+        Ok(BuildSummary { pages: 5, posts: 7 })
+    );
+
+    let read = |path: &str| fs::read_to_string(out.join(path)).unwrap();
+    assert_in_order(
+        &read("tag/rust/index.html"),
+        &["Tagged Rust", "Post 06", "Post 02"],
+    );
+    assert!(read("tag/rust/before/2/index.html").contains("Post 01"));
+    assert!(read("tag/web-dev/index.html").contains("Post 07"));
+    for format in ["rss", "atom", "json"] {
+        let feed = read(&format!("tag/rust/feed.{}", format));
+        assert!(feed.contains("Post 06"));
+        assert!(!feed.contains("Post 07"));
+    }
+
+    for file in html_files(&out) {
+        let html = fs::read_to_string(&file).unwrap();
+        for link in internal_links(&html) {
+            let target = out.join(link);
+            let exists = if link.rsplit('/').next().unwrap().contains('.') {
+                target.is_file()
+            } else {
+                target.join("index.html").is_file()
+            };
+            assert!(exists, "{} links to missing /{}", file.display(), link);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use super::*;
+use crate::export::{export_posts, import_posts};
+use std::fs;
+
+#[actix_web::test]
+async fn edited_posts_are_rendered_again() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    let post = seed_post(&db, &jane, 1, blog_posts::PostStatus::Published).await;
+
+    // Render once so the post is cached
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains("Body of <strong>post 1</strong>"));
+    let (_, html) = get(&db, "/").await;
+    assert!(html.contains("Body of <strong>post 1</strong>"));
+
+    let res = call(
+        &db,
+        TestRequest::post()
+            .uri(&format!("/admin/posts/{}/edit", post.post_id))
+            .cookie(login(&db, "jane").await)
+            .set_form([
+                ("title", "Post 01"),
+                ("description", ""),
+                ("body", "An *edited* body"),
+                ("slug", "post-01"),
+                ("publish", "true"),
+            ]),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains("An <em>edited</em> body"));
+    let (_, html) = get(&db, "/").await;
+    assert!(html.contains("An <em>edited</em> body"));
+    let (_, rss) = get(&db, "/feed.rss").await;
+    assert!(rss.contains("edited"));
+    assert!(!rss.contains("post 1"));
+
+    // Imports can keep `last_updated` as it was
+    let dir = std::env::temp_dir().join(format!("b5-cache-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    export_posts(&db, &dir).await.unwrap();
+    let path = dir.join("post-01.md");
+    let file = fs::read_to_string(&path).unwrap();
+    fs::write(
+        &path,
+        file.replace("An *edited* body", "An **imported** body"),
+    )
+    .unwrap();
+    import_posts(&db, &dir).await.unwrap();
+
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains("An <strong>imported</strong> body"));
+}
@@ -0,0 +1,91 @@
+use super::*;
+use crate::{
+    cli::{create_user, delete_user, find_user, list_users, set_password, set_role},
+    entities::prelude::*,
+};
+use sea_orm::EntityTrait;
+
+async fn logs_in(db: &DatabaseConnection, username: &str, password: &str) -> bool {
+    let res = call(
+        db,
+        TestRequest::post()
+            .uri("/login")
+            .set_form([("username", username), ("password", password)]),
+    )
+    .await;
+    res.status() == StatusCode::SEE_OTHER
+}
+
+#[actix_web::test]
+async fn create_and_change_users() {
+    let db = setup().await;
+
+    let admin = create_user(&db, " root ", "first password", Role::Admin)
+        .await
+        .unwrap();
+    assert_eq!(admin.username, "root");
+    assert_eq!(Role::from(admin.level), Role::Admin);
+    assert!(logs_in(&db, "root", "first password").await);
+    assert_eq!(
+        create_user(&db, "root", "other", Role::Author)
+            .await
+            .unwrap_err(),
+        "root is already taken"
+    );
+    assert!(create_user(&db, "", "password", Role::Author)
+        .await
+        .is_err());
+
+    set_password(&db, admin, "second password").await.unwrap();
+    assert!(!logs_in(&db, "root", "first password").await);
+    assert!(logs_in(&db, "root", "second password").await);
+
+    let admin = find_user(&db, "root").await.unwrap();
+    set_role(&db, admin, Role::Commenter).await.unwrap();
+    create_user(&db, "jane", "password", Role::Author)
+        .await
+        .unwrap();
+    let users = list_users(&db).await.unwrap();
+    assert_eq!(
+        users
+            .iter()
+            .map(|user| (user.username.as_str(), Role::from(user.level)))
+            .collect::<Vec<_>>(),
+        [("root", Role::Commenter), ("jane", Role::Author)]
+    );
+    assert_eq!(
+        find_user(&db, "nobody").await.unwrap_err(),
+        "There is no user called nobody"
+    );
+}
+
+#[actix_web::test]
+async fn deleting_users_keeps_their_posts() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    let john = seed_user(&db, "john", Role::Author).await;
+    seed_user(&db, "reader", Role::Commenter).await;
+    seed_posts(&db, &jane, 2).await;
+
+    assert_eq!(delete_user(&db, "reader", None).await, Ok(0));
+    assert!(delete_user(&db, "jane", None)
+        .await
+        .unwrap_err()
+        .starts_with("jane has 2 posts"));
+    assert!(delete_user(&db, "jane", Some("jane")).await.is_err());
+    assert!(delete_user(&db, "jane", Some("nobody")).await.is_err());
+
+    assert_eq!(delete_user(&db, "jane", Some("john")).await, Ok(2));
+    let posts = BlogPosts::find().all(&db).await.unwrap();
+    assert_eq!(posts.len(), 2);
+    assert!(posts.iter().all(|post| post.author_id == john.user_id));
+    assert_eq!(
+        list_users(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|user| user.username)
+            .collect::<Vec<_>>(),
+        ["john"]
+    );
+}
@@ -0,0 +1,278 @@
+use super::*;
+use crate::entities::{blog_posts::PostStatus, comments::CommentStatus, prelude::Comments};
+use actix_web::http::header;
+use sea_orm::EntityTrait;
+
+/// Posts a comment as a guest, or as whoever `cookie` belongs to
+fn comment(post_id: u32, form: &[(&str, &str)], cookie: Option<Cookie<'static>>) -> TestRequest {
+    let req = TestRequest::post()
+        .uri(&format!("/post/{}/comments", post_id))
+        .set_form(form);
+    match cookie {
+        Some(cookie) => req.cookie(cookie),
+        None => req,
+    }
+}
+
+#[actix_web::test]
+async fn comment_links_cannot_run_scripts() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    seed_user(&db, "mod", Role::Mod).await;
+    let post = seed_post(&db, &jane, 1, PostStatus::Published).await;
+
+    let body = "[click](javascript:alert(document.cookie)) and [home](https://example.com)";
+    let res = call(
+        &db,
+        comment(post.post_id, &[("name", "Eve"), ("body", body)], None),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+
+    let cookie = login(&db, "mod").await;
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri("/admin/comments")
+            .cookie(cookie.clone()),
+    )
+    .await;
+    let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(html.contains(">click</a>"), "{}", html);
+    assert!(html.contains("https://example.com"));
+    assert!(!html.contains("javascript:"));
+
+    let res = call(
+        &db,
+        TestRequest::post()
+            .uri("/admin/comments")
+            .cookie(cookie)
+            .set_form([("action", "approve"), ("comment", "1")]),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains(">click</a>"), "{}", html);
+    assert!(html.contains("https://example.com"));
+    assert!(!html.contains("javascript:"));
+}
+
+#[actix_web::test]
+async fn guests_need_a_name_and_comments_have_limits() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    let post = seed_post(&db, &jane, 1, PostStatus::Published).await;
+
+    let long_name = "n".repeat(61);
+    let long_body = "b".repeat(5001);
+    for (form, error) in [
+        (vec![("body", "Hello")], "Please enter a name"),
+        (
+            vec![("name", "  "), ("body", "Hello")],
+            "Please enter a name",
+        ),
+        (
+            vec![("name", long_name.as_str()), ("body", "Hello")],
+            "Names may not be longer",
+        ),
+        (
+            vec![("name", "Eve"), ("body", " ")],
+            "Comments may not be empty",
+        ),
+        (
+            vec![("name", "Eve"), ("body", long_body.as_str())],
+            "Comments may not be longer",
+        ),
+    ] {
+        let res = call(&db, comment(post.post_id, &form, None)).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{:?}", form);
+        let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+        assert!(html.contains(error), "{:?}", form);
+    }
+
+    // Members comment under their username, so they need no name
+    let cookie = login(&db, "jane").await;
+    let res = call(
+        &db,
+        comment(post.post_id, &[("body", "Hello")], Some(cookie)),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+
+    // Exactly at the limits is fine
+    let name = "n".repeat(60);
+    let body = "b".repeat(5000);
+    let form = [("name", name.as_str()), ("body", body.as_str())];
+    let res = call(&db, comment(post.post_id, &form, None)).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+
+    assert_eq!(Comments::find().all(&db).await.unwrap().len(), 2);
+}
+
+#[actix_web::test]
+async fn guest_comments_wait_for_approval() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    seed_user(&db, "reader", Role::Commenter).await;
+    let post = seed_post(&db, &jane, 1, PostStatus::Published).await;
+
+    let form = [("name", "Guest"), ("body", "Guest says hi")];
+    let res = call(&db, comment(post.post_id, &form, None)).await;
+    assert_eq!(
+        res.headers().get(header::LOCATION).unwrap(),
+        "/post/post-01?pending=true#comment-form"
+    );
+    let cookie = login(&db, "reader").await;
+    let form = [("body", "Reader says hi")];
+    call(&db, comment(post.post_id, &form, Some(cookie))).await;
+    let cookie = login(&db, "jane").await;
+    let form = [("body", "Jane says hi")];
+    let res = call(&db, comment(post.post_id, &form, Some(cookie))).await;
+    assert_eq!(
+        res.headers().get(header::LOCATION).unwrap(),
+        "/post/post-01#comment-3"
+    );
+
+    let statuses: Vec<_> = Comments::find()
+        .all(&db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|comment| comment.status)
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            CommentStatus::Pending,
+            CommentStatus::Pending,
+            CommentStatus::Approved
+        ]
+    );
+
+    // Only the approved comment shows on the post
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains("Jane says hi"));
+    assert!(!html.contains("Guest says hi"));
+    assert!(!html.contains("Reader says hi"));
+    let (_, html) = get(&db, "/post/post-01?pending=true").await;
+    assert!(html.contains("will appear once a moderator approves it"));
+}
+
+#[actix_web::test]
+async fn comments_on_drafts_are_not_found() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    let draft = seed_post(&db, &jane, 1, PostStatus::Draft).await;
+
+    let form = [("name", "Guest"), ("body", "Hello")];
+    let res = call(&db, comment(draft.post_id, &form, None)).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = call(&db, comment(999, &form, None)).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    assert!(Comments::find().all(&db).await.unwrap().is_empty());
+}
+
+/// Applies a moderation action to the given comments as whoever `cookie` belongs to
+fn moderate(action: &str, ids: &[u32], cookie: Option<Cookie<'static>>) -> TestRequest {
+    let mut form = vec![("action".to_owned(), action.to_owned())];
+    form.extend(ids.iter().map(|id| ("comment".to_owned(), id.to_string())));
+    let req = TestRequest::post().uri("/admin/comments").set_form(form);
+    match cookie {
+        Some(cookie) => req.cookie(cookie),
+        None => req,
+    }
+}
+
+#[actix_web::test]
+async fn mods_approve_reject_and_delete_in_bulk() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    seed_user(&db, "mod", Role::Mod).await;
+    let post = seed_post(&db, &jane, 1, PostStatus::Published).await;
+    for n in 1..=5 {
+        let body = format!("Comment number {}", n);
+        let form = [("name", "Guest"), ("body", body.as_str())];
+        call(&db, comment(post.post_id, &form, None)).await;
+    }
+
+    // Authors can't moderate
+    let cookie = login(&db, "jane").await;
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri("/admin/comments")
+            .cookie(cookie.clone()),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = call(&db, moderate("approve", &[1, 2], Some(cookie))).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = call(&db, moderate("approve", &[1, 2], None)).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let cookie = login(&db, "mod").await;
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri("/admin/comments")
+            .cookie(cookie.clone()),
+    )
+    .await;
+    let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert_in_order(
+        &html,
+        &[
+            "Comment number 1",
+            "Comment number 2",
+            "Comment number 3",
+            "Comment number 4",
+            "Comment number 5",
+        ],
+    );
+
+    let res = call(&db, moderate("approve", &[1, 2], Some(cookie.clone()))).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(
+        res.headers().get(header::LOCATION).unwrap(),
+        "/admin/comments?status=pending"
+    );
+    call(&db, moderate("reject", &[3], Some(cookie.clone()))).await;
+    call(&db, moderate("delete", &[4], Some(cookie.clone()))).await;
+    let res = call(&db, moderate("publish", &[5], Some(cookie.clone()))).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let statuses: Vec<_> = Comments::find()
+        .all(&db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|comment| (comment.comment_id, comment.status))
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            (1, CommentStatus::Approved),
+            (2, CommentStatus::Approved),
+            (3, CommentStatus::Spam),
+            (5, CommentStatus::Pending),
+        ]
+    );
+
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert_in_order(&html, &["Comment number 1", "Comment number 2"]);
+    assert!(!html.contains("Comment number 3"));
+    assert!(!html.contains("Comment number 5"));
+
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri("/admin/comments?status=spam")
+            .cookie(cookie),
+    )
+    .await;
+    let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(html.contains("Comment number 3"));
+    assert!(!html.contains("Comment number 5"));
+}
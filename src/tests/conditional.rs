@@ -0,0 +1,284 @@
+use super::*;
+use crate::entities::{comments, prelude::*};
+use actix_web::http::header::{self, HeaderMap};
+
+#[track_caller]
+fn value(headers: &HeaderMap, name: header::HeaderName) -> &str {
+    headers.get(name).unwrap().to_str().unwrap()
+}
+
+/// The status and a header of the response to `req`
+async fn header_of(
+    db: &DatabaseConnection,
+    req: TestRequest,
+    name: header::HeaderName,
+) -> (StatusCode, Option<String>) {
+    let res = call(db, req).await;
+    let value = res
+        .headers()
+        .get(name)
+        .map(|value| value.to_str().unwrap().to_owned());
+    (res.status(), value)
+}
+
+async fn etag(db: &DatabaseConnection, uri: &str) -> String {
+    let (status, etag) = header_of(db, TestRequest::get().uri(uri), header::ETAG).await;
+    assert_eq!(status, StatusCode::OK);
+    etag.unwrap()
+}
+
+#[actix_web::test]
+async fn feeds_answer_conditional_requests() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 2).await;
+
+    let res = call(&db, TestRequest::get().uri("/feed.rss")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let headers = res.headers();
+    assert_eq!(value(headers, header::CACHE_CONTROL), "public, max-age=300");
+    // The newest post is from two hours after the first seeded date
+    assert_eq!(
+        value(headers, header::LAST_MODIFIED),
+        "Sat, 01 Jan 2022 02:00:00 GMT"
+    );
+    let etag = value(headers, header::ETAG).to_owned();
+    assert!(etag.starts_with("W/\""));
+
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri("/feed.rss")
+            .insert_header((header::IF_NONE_MATCH, etag.as_str())),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(value(res.headers(), header::ETAG), etag.as_str());
+    assert!(test::read_body(res).await.is_empty());
+
+    let since = |date: &str| {
+        TestRequest::get()
+            .uri("/feed.rss")
+            .insert_header((header::IF_MODIFIED_SINCE, date.to_owned()))
+    };
+    let (status, _) = header_of(&db, since("Sat, 01 Jan 2022 02:00:00 GMT"), header::ETAG).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    let (status, _) = header_of(&db, since("Sat, 01 Jan 2022 01:59:59 GMT"), header::ETAG).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A stale tag wins over a date that would still match
+    let res = call(
+        &db,
+        since("Sat, 01 Jan 2022 02:00:00 GMT").insert_header((header::IF_NONE_MATCH, "W/\"old\"")),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    seed_post(&db, &author, 3, blog_posts::PostStatus::Published).await;
+    assert_ne!(self::etag(&db, "/feed.rss").await, etag);
+}
+
+#[actix_web::test]
+async fn pages_change_tags_when_their_posts_do() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 6).await;
+
+    let listing = etag(&db, "/posts").await;
+    let older = etag(&db, "/posts/before/2").await;
+    let post = etag(&db, "/post/post-01").await;
+    assert_ne!(listing, older);
+    let (status, cache_control) = header_of(
+        &db,
+        TestRequest::get()
+            .uri("/posts")
+            .insert_header((header::IF_NONE_MATCH, listing.as_str())),
+        header::CACHE_CONTROL,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert_eq!(cache_control.unwrap(), "public, max-age=0");
+
+    // Editing a post that's only on the older page leaves the first page alone
+    let mut first: blog_posts::ActiveModel = BlogPosts::find_by_id(1)
+        .one(&db)
+        .await
+        .unwrap()
+        .unwrap()
+        .into();
+    first.body = Set("Changed".to_owned());
+    first.last_updated = Set(Some(date(10)));
+    first.update(&db).await.unwrap();
+    assert_eq!(etag(&db, "/posts").await, listing);
+    assert_ne!(etag(&db, "/posts/before/2").await, older);
+    assert_ne!(etag(&db, "/post/post-01").await, post);
+}
+
+#[actix_web::test]
+async fn scheduled_posts_going_live_are_modifications() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_post(&db, &author, 4, blog_posts::PostStatus::Published).await;
+    // Last edited before the other post was published, and goes live after it
+    let mut scheduled: blog_posts::ActiveModel =
+        seed_post(&db, &author, 2, blog_posts::PostStatus::Scheduled)
+            .await
+            .into();
+    scheduled.last_updated = Set(Some(date(3)));
+    scheduled.post_date = Set(chrono::Utc::now().naive_utc() + Duration::days(1));
+    let scheduled = scheduled.update(&db).await.unwrap();
+
+    let mut seen = Vec::new();
+    for uri in ["/posts", "/feed.rss"] {
+        let (status, modified) =
+            header_of(&db, TestRequest::get().uri(uri), header::LAST_MODIFIED).await;
+        assert_eq!(status, StatusCode::OK);
+        seen.push((uri, modified.unwrap()));
+    }
+
+    // Its date passes
+    let mut live: blog_posts::ActiveModel = scheduled.into();
+    live.post_date = Set(date(5));
+    live.update(&db).await.unwrap();
+
+    for (uri, modified) in seen {
+        let res = call(
+            &db,
+            TestRequest::get()
+                .uri(uri)
+                .insert_header((header::IF_MODIFIED_SINCE, modified)),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+        assert_eq!(
+            value(res.headers(), header::LAST_MODIFIED),
+            "Sat, 01 Jan 2022 05:00:00 GMT"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn signed_in_users_get_private_pages() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 1).await;
+
+    let (_, cache_control) = header_of(
+        &db,
+        TestRequest::get().uri("/post/post-01"),
+        header::CACHE_CONTROL,
+    )
+    .await;
+    assert_eq!(cache_control.unwrap(), "public, max-age=0");
+
+    let cookie = login(&db, "author").await;
+    let (_, cache_control) = header_of(
+        &db,
+        TestRequest::get().uri("/post/post-01").cookie(cookie),
+        header::CACHE_CONTROL,
+    )
+    .await;
+    assert_eq!(cache_control.unwrap(), "private, no-cache");
+}
+
+#[actix_web::test]
+async fn pages_vary_by_cookie() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 1).await;
+
+    for uri in ["/posts", "/post/post-01"] {
+        let res = call(&db, TestRequest::get().uri(uri)).await;
+        assert_eq!(value(res.headers(), header::VARY), "Cookie", "{}", uri);
+        let etag = value(res.headers(), header::ETAG).to_owned();
+
+        let res = call(
+            &db,
+            TestRequest::get()
+                .uri(uri)
+                .insert_header((header::IF_NONE_MATCH, etag)),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(value(res.headers(), header::VARY), "Cookie", "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn moderating_comments_changes_the_post_page() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    let post = seed_post(&db, &author, 1, blog_posts::PostStatus::Published).await;
+    // Written before the post was last changed, and approved after
+    let comment = comments::ActiveModel {
+        post_id: Set(post.post_id),
+        anon_name: Set(Some("Guest".to_owned())),
+        body: Set("Old comment".to_owned()),
+        comment_date: Set(date(0)),
+        status: Set(comments::CommentStatus::Pending),
+        ..Default::default()
+    }
+    .insert(&db)
+    .await
+    .unwrap();
+
+    let (status, modified) = header_of(
+        &db,
+        TestRequest::get().uri("/post/post-01"),
+        header::LAST_MODIFIED,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(modified, None);
+    let pending = etag(&db, "/post/post-01").await;
+
+    let mut approved: comments::ActiveModel = comment.into();
+    approved.status = Set(comments::CommentStatus::Approved);
+    let approved = approved.update(&db).await.unwrap();
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri("/post/post-01")
+            .insert_header((header::IF_NONE_MATCH, pending.as_str())),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    // The approval left every date alone, so a date can't tell the copy is stale
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri("/post/post-01")
+            .insert_header((header::IF_MODIFIED_SINCE, "Sat, 01 Jan 2022 01:00:00 GMT")),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let shown = etag(&db, "/post/post-01").await;
+    assert_ne!(shown, pending);
+
+    approved.delete(&db).await.unwrap();
+    let deleted = etag(&db, "/post/post-01").await;
+    assert_ne!(deleted, shown);
+    assert_eq!(deleted, pending);
+}
+
+#[actix_web::test]
+async fn static_files_can_be_cached() {
+    let db = setup().await;
+
+    let res = call(&db, TestRequest::get().uri("/static/main.css")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        value(res.headers(), header::CACHE_CONTROL),
+        "public, max-age=86400"
+    );
+    let etag = value(res.headers(), header::ETAG).to_owned();
+
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri("/static/main.css")
+            .insert_header((header::IF_NONE_MATCH, etag)),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+}
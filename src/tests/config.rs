@@ -0,0 +1,257 @@
+use crate::{auth::Role, config::load_from};
+use std::path::{Path, PathBuf};
+
+/// The TOML sample up to its `[server]` section
+fn sample_without_server() -> String {
+    let sample = std::fs::read_to_string("./sample_config.toml").unwrap();
+    sample[..sample.find("[server]").unwrap()].to_owned()
+}
+
+fn vars(vars: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+    vars.iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Writes `contents` to a file in the temp dir that no other test uses
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("b5-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn json_and_toml_samples_match() {
+    let json = load_from(Some(Path::new("./sample_config.json")), vars(&[])).unwrap();
+    let toml = load_from(Some(Path::new("./sample_config.toml")), vars(&[])).unwrap();
+
+    assert_eq!(
+        serde_json::to_value(json).unwrap(),
+        serde_json::to_value(toml).unwrap()
+    );
+}
+
+#[test]
+fn env_overrides_file() {
+    let config = load_from(
+        Some(Path::new("./sample_config.toml")),
+        vars(&[
+            ("B5_PAGE_SIZE", "12"),
+            ("B5_WEBMASTER_NAME", "John Doe"),
+            ("B5_TAGLINES", "First | Second"),
+            ("B5_UNKNOWN", "ignored"),
+            ("PAGE_SIZE", "3"),
+            ("B5_MARKDOWN_TABLES", "true"),
+            ("B5_MARKDOWN_TOC_MIN_HEADINGS", "4"),
+        ]),
+    )
+    .unwrap();
+
+    assert_eq!(config.page_size, 12);
+    assert_eq!(config.webmaster.name, "John Doe");
+    assert_eq!(config.webmaster.email, "example@example.com");
+    assert_eq!(config.taglines, ["First", "Second"]);
+    assert_eq!(config.title, "My blog");
+    assert!(config.markdown.tables);
+    assert!(!config.markdown.footnotes);
+    assert_eq!(config.markdown.toc_min_headings, Some(4));
+}
+
+#[test]
+fn reports_every_problem() {
+    let path = temp_file(
+        "invalid.json",
+        r#"{
+            "title": "My blog",
+            "url": "https://www.example.com",
+            "description": "Description",
+            "webmaster": { "email": "example@example.com", "name": "Jane Doe" },
+            "taglines": [],
+            "preview_size": 400,
+            "page_size": 0,
+            "rss_size": 25,
+            "lang": "en-us"
+        }"#,
+    );
+    let errors = load_from(
+        Some(&path),
+        vars(&[("B5_RSS_SIZE", "lots"), ("B5_MARKDOWN_FOOTNOTES", "yes")]),
+    )
+    .unwrap_err()
+    .0;
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(
+        errors,
+        [
+            "B5_RSS_SIZE must be a whole number",
+            "B5_MARKDOWN_FOOTNOTES must be true or false",
+            "`url` must end with a slash",
+            "`taglines` may not be empty",
+            "`page_size` must be at least 1",
+        ]
+    );
+}
+
+#[test]
+fn reports_missing_fields_and_bad_files() {
+    let path = temp_file("partial.toml", "title = \"My blog\"\n");
+    let errors = load_from(Some(&path), vars(&[])).unwrap_err().0;
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(errors.len(), 9);
+    assert_eq!(
+        errors[0],
+        "`url` is missing. Set it in the config file or with B5_URL."
+    );
+    assert_eq!(
+        errors[2],
+        "`webmaster.email` is missing. Set it in the config file or with B5_WEBMASTER_EMAIL."
+    );
+
+    let errors = load_from(Some(Path::new("./no-such-config.json")), vars(&[]))
+        .unwrap_err()
+        .0;
+    assert!(errors[0].starts_with("Could not read ./no-such-config.json"));
+}
+
+#[test]
+fn server_defaults_and_overrides() {
+    let path = temp_file("no-server.toml", &sample_without_server());
+    let config = load_from(Some(&path), vars(&[])).unwrap();
+    assert_eq!(config.server.address, "127.0.0.1");
+    assert_eq!(config.server.port, 8080);
+    assert!(config.server.workers.is_none());
+    assert!(config.server.tls.is_none());
+
+    let config = load_from(
+        Some(&path),
+        vars(&[
+            ("B5_SERVER_ADDRESS", "0.0.0.0"),
+            ("B5_SERVER_PORT", "80"),
+            ("B5_SERVER_WORKERS", "2"),
+            ("B5_SERVER_KEEP_ALIVE", "0"),
+            ("B5_SERVER_UNIX_SOCKET", "/run/b5.sock"),
+        ]),
+    )
+    .unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(config.server.address, "0.0.0.0");
+    assert_eq!(config.server.port, 80);
+    assert_eq!(config.server.workers, Some(2));
+    assert_eq!(config.server.keep_alive, Some(0));
+    assert_eq!(
+        config.server.unix_socket.as_deref(),
+        Some(Path::new("/run/b5.sock"))
+    );
+}
+
+#[test]
+fn checks_server_settings() {
+    let errors = load_from(
+        Some(Path::new("./sample_config.toml")),
+        vars(&[
+            ("B5_SERVER_WORKERS", "0"),
+            ("B5_SERVER_TLS_CERT", "./no-such-cert.pem"),
+            ("B5_SERVER_TLS_KEY", "./Cargo.toml"),
+            ("B5_SERVER_UNIX_SOCKET", "/run/b5.sock"),
+        ]),
+    )
+    .unwrap_err()
+    .0;
+
+    assert_eq!(
+        errors,
+        [
+            "`server.workers` must be at least 1",
+            "`server.tls.cert` is not a file that can be read",
+            "`server.unix_socket` can't be used with `server.tls`. Let the proxy in front handle TLS.",
+        ]
+    );
+
+    let errors = load_from(
+        Some(Path::new("./sample_config.toml")),
+        vars(&[("B5_SERVER_PORT", "70000")]),
+    )
+    .unwrap_err()
+    .0;
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("70000"));
+}
+
+#[test]
+fn html_allow_list() {
+    let config = load_from(
+        Some(Path::new("./sample_config.toml")),
+        vars(&[
+            ("B5_HTML_TRUSTED_ROLE", "author"),
+            ("B5_HTML_TAGS", "kbd | video"),
+            (
+                "B5_HTML_ATTRIBUTES",
+                "img=loading ; video=controls, poster;",
+            ),
+        ]),
+    )
+    .unwrap();
+    assert_eq!(config.html.trusted_role, Some(Role::Author));
+    assert_eq!(config.html.tags, ["kbd", "video"]);
+    assert_eq!(config.html.attributes["img"], ["loading"]);
+    assert_eq!(config.html.attributes["video"], ["controls", "poster"]);
+
+    let errors = load_from(
+        Some(Path::new("./sample_config.toml")),
+        vars(&[("B5_HTML_TAGS", "details|script")]),
+    )
+    .unwrap_err()
+    .0;
+    assert_eq!(errors, ["`html` can't allow the script tag"]);
+
+    let errors = load_from(
+        Some(Path::new("./sample_config.toml")),
+        vars(&[("B5_HTML_TRUSTED_ROLE", "everyone")]),
+    )
+    .unwrap_err()
+    .0;
+    assert!(errors[0].contains("unknown variant `everyone`"));
+
+    let errors = load_from(
+        Some(Path::new("./sample_config.toml")),
+        vars(&[("B5_HTML_ATTRIBUTES", "img loading")]),
+    )
+    .unwrap_err()
+    .0;
+    assert_eq!(
+        errors,
+        ["B5_HTML_ATTRIBUTES must look like key=a,b;other=c"]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_path_must_be_free_or_a_socket() {
+    let file = temp_file("not-a-socket", "");
+    let errors = load_from(
+        Some(Path::new("./sample_config.toml")),
+        vars(&[("B5_SERVER_UNIX_SOCKET", file.to_str().unwrap())]),
+    )
+    .unwrap_err()
+    .0;
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(
+        errors,
+        ["`server.unix_socket` is a file that isn't a socket"]
+    );
+
+    let socket = std::env::temp_dir().join(format!("b5-{}-stale.sock", std::process::id()));
+    let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+    drop(listener);
+    for path in [&socket, &file] {
+        load_from(
+            Some(Path::new("./sample_config.toml")),
+            vars(&[("B5_SERVER_UNIX_SOCKET", path.to_str().unwrap())]),
+        )
+        .unwrap();
+    }
+    std::fs::remove_file(socket).unwrap();
+}
@@ -0,0 +1,162 @@
+use super::*;
+use crate::{
+    entities::prelude::*,
+    export::{export_posts, import_posts, ImportSummary},
+    tags::{set_post_tags, tags_for_post},
+};
+use sea_orm::{ConnectionTrait, EntityTrait, PaginatorTrait};
+use std::{fs, path::PathBuf};
+
+/// An empty directory for a test to write to
+fn export_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("b5-export-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[actix_web::test]
+async fn exported_posts_import_into_another_database() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    let post = seed_post(&db, &jane, 1, blog_posts::PostStatus::Published).await;
+    seed_post(&db, &jane, 2, blog_posts::PostStatus::Draft).await;
+    let mut edited: blog_posts::ActiveModel = post.into();
+    edited.description = Set(Some("About \"post\" one".to_owned()));
+    edited.body = Set("+++\n\nBody with a fence".to_owned());
+    edited.last_updated = Set(Some(date(5)));
+    edited.preview_size = Set(Some(20));
+    let post = edited.update(&db).await.unwrap();
+    set_post_tags(&db, post.post_id, "Rust, Web").await.unwrap();
+
+    let dir = export_dir("round-trip");
+    assert_eq!(export_posts(&db, &dir).await, Ok(2));
+    let file = fs::read_to_string(dir.join("post-01.md")).unwrap();
+    assert!(file.starts_with("+++\ntitle = \"Post 01\"\n"));
+    assert!(file.contains("author = \"jane\"\n"));
+    assert!(file.contains("post_date = \"2022-01-01T01:00:00\"\n"));
+    assert!(file.contains("last_updated = \"2022-01-01T05:00:00\"\n"));
+    assert!(file.ends_with("+++\n\n+++\n\nBody with a fence"));
+
+    let other = setup().await;
+    seed_user(&other, "someone", Role::Author).await;
+    seed_user(&other, "jane", Role::Author).await;
+    assert_eq!(
+        import_posts(&other, &dir).await,
+        Ok(ImportSummary {
+            created: 2,
+            updated: 0
+        })
+    );
+
+    let original = BlogPosts::find().all(&db).await.unwrap();
+    let imported = BlogPosts::find().all(&other).await.unwrap();
+    for (a, b) in original.iter().zip(&imported) {
+        assert_eq!(
+            (&a.slug, &a.title, &a.description, &a.body, a.status),
+            (&b.slug, &b.title, &b.description, &b.body, b.status)
+        );
+        assert_eq!(
+            (a.post_date, a.last_updated, a.preview_size),
+            (b.post_date, b.last_updated, b.preview_size)
+        );
+    }
+    // Authors are matched by name, not id
+    let other_jane = Users::find_by_id(2).one(&other).await.unwrap().unwrap();
+    assert!(imported
+        .iter()
+        .all(|post| post.author_id == other_jane.user_id));
+    let tags = tags_for_post(&other, imported[0].post_id).await.unwrap();
+    assert_eq!(
+        tags.into_iter().map(|tag| tag.name).collect::<Vec<_>>(),
+        ["Rust", "Web"]
+    );
+    assert_eq!(PostRevisions::find().count(&other).await.unwrap(), 2);
+}
+
+#[actix_web::test]
+async fn import_updates_posts_by_slug() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    seed_user(&db, "john", Role::Author).await;
+    seed_posts(&db, &jane, 2).await;
+
+    let dir = export_dir("update");
+    export_posts(&db, &dir).await.unwrap();
+    let path = dir.join("post-02.md");
+    let file = fs::read_to_string(&path)
+        .unwrap()
+        .replace("jane", "john")
+        .replace("**post 2**", "the second post");
+    fs::write(&path, file).unwrap();
+
+    assert_eq!(
+        import_posts(&db, &dir).await,
+        Ok(ImportSummary {
+            created: 0,
+            updated: 2
+        })
+    );
+    let posts = BlogPosts::find().all(&db).await.unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[1].body, "Body of the second post");
+    assert_eq!(posts[1].author_id, 2);
+    assert_eq!(posts[0].author_id, jane.user_id);
+    // Only the post whose content changed gets a revision
+    assert_eq!(PostRevisions::find().count(&db).await.unwrap(), 1);
+}
+
+#[actix_web::test]
+async fn bad_files_stop_the_whole_import() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    seed_posts(&db, &jane, 1).await;
+
+    let dir = export_dir("bad");
+    export_posts(&db, &dir).await.unwrap();
+    let file = fs::read_to_string(dir.join("post-01.md")).unwrap();
+    fs::write(dir.join("a.md"), file.replace("Post 01", "Copy")).unwrap();
+    fs::write(
+        dir.join("b.md"),
+        file.replace("jane", "nobody").replace("post-01", "other"),
+    )
+    .unwrap();
+    fs::write(dir.join("c.md"), "No front matter").unwrap();
+    fs::write(dir.join("d.md"), file.replace("post-01", "Not A Slug")).unwrap();
+    fs::write(dir.join("notes.txt"), "Not a post").unwrap();
+
+    let errors = import_posts(&db, &dir).await.unwrap_err();
+    let errors: Vec<_> = errors.lines().collect();
+    assert_eq!(errors.len(), 4);
+    assert!(errors[0].ends_with("b.md: there is no user called nobody"));
+    assert!(errors[1].contains("c.md: The file has to start with front matter"));
+    assert!(errors[2].contains("d.md: `Not A Slug` is not a valid slug"));
+    assert!(errors[3].ends_with("post-01.md: another file also has the slug post-01"));
+    assert_eq!(BlogPosts::find().count(&db).await.unwrap(), 1);
+}
+
+#[actix_web::test]
+async fn failed_imports_write_nothing() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    seed_posts(&db, &jane, 2).await;
+    set_post_tags(&db, 2, "Rust").await.unwrap();
+
+    let dir = export_dir("failed");
+    export_posts(&db, &dir).await.unwrap();
+    let other = setup().await;
+    seed_user(&other, "jane", Role::Author).await;
+    // The second post's tags fail to save after the first post was written
+    other
+        .execute(sea_orm::Statement::from_string(
+            other.get_database_backend(),
+            "CREATE TRIGGER no_tags BEFORE INSERT ON post_tags BEGIN SELECT RAISE(ABORT, 'no tags'); END"
+                .to_owned(),
+        ))
+        .await
+        .unwrap();
+
+    let error = import_posts(&other, &dir).await.unwrap_err();
+    assert!(error.contains("no tags"));
+    assert_eq!(BlogPosts::find().count(&other).await.unwrap(), 0);
+    assert_eq!(PostRevisions::find().count(&other).await.unwrap(), 0);
+}
@@ -0,0 +1,210 @@
+use super::*;
+use crate::entities::{blog_posts::PostStatus, prelude::*};
+use actix_web::http::header;
+use quick_xml::{events::Event, Reader};
+use serde_json::Value;
+
+/// The text of every `tag` element in `xml`, which has to be well formed
+#[track_caller]
+fn xml_texts(xml: &str, tag: &str) -> Vec<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut texts = Vec::new();
+    let mut inside = false;
+    loop {
+        match reader
+            .read_event()
+            .expect("the feed should be well formed xml")
+        {
+            Event::Start(start) => inside = start.name().as_ref() == tag.as_bytes(),
+            Event::Text(text) if inside => texts.push(text.unescape().unwrap().into_owned()),
+            Event::End(_) => inside = false,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    texts
+}
+
+/// Seeds three published posts and a draft. Post 2 was edited after the others were published.
+async fn seed_feed(db: &DatabaseConnection) {
+    let author = seed_user(db, "jane", Role::Author).await;
+    seed_posts(db, &author, 3).await;
+    seed_post(db, &author, 4, PostStatus::Draft).await;
+    let mut edited: blog_posts::ActiveModel = BlogPosts::find_by_id(2)
+        .one(db)
+        .await
+        .unwrap()
+        .unwrap()
+        .into();
+    edited.last_updated = Set(Some(date(10)));
+    edited.update(db).await.unwrap();
+}
+
+#[actix_web::test]
+async fn rss() {
+    let db = setup().await;
+    let author = seed_user(&db, "jane", Role::Author).await;
+    seed_posts(&db, &author, 3).await;
+    seed_post(&db, &author, 4, PostStatus::Draft).await;
+
+    let res = call(&db, TestRequest::get().uri("/feed.rss")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("application/rss+xml"));
+
+    let xml = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(xml.starts_with("<?xml"));
+    assert!(xml.contains("<title>My blog</title>"));
+    assert_in_order(
+        &xml,
+        &[
+            "<title>Post 03</title>",
+            "<link>https://www.example.com/post/post-03</link>",
+            "<pubDate>Sat, 01 Jan 2022 03:00:00 +0000</pubDate>",
+            "<dc:creator>jane</dc:creator>",
+            "<title>Post 02</title>",
+            "<title>Post 01</title>",
+        ],
+    );
+    // Bodies are rendered to HTML and escaped into the XML
+    assert!(xml.contains("&lt;strong&gt;post 3&lt;/strong&gt;"));
+    assert!(!xml.contains("Post 04"));
+}
+
+#[actix_web::test]
+async fn empty_rss() {
+    let db = setup().await;
+
+    let (status, xml) = get(&db, "/feed.rss").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(xml.contains("<channel>"));
+    assert!(!xml.contains("<item>"));
+}
+
+#[actix_web::test]
+async fn atom() {
+    let db = setup().await;
+    seed_feed(&db).await;
+
+    let res = call(&db, TestRequest::get().uri("/feed.atom")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("application/atom+xml"));
+
+    let xml = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert_eq!(
+        xml_texts(&xml, "title"),
+        ["My blog", "Post 03", "Post 02", "Post 01"]
+    );
+    // The feed was last updated when its newest edit was made
+    assert_eq!(
+        xml_texts(&xml, "updated"),
+        [
+            "2022-01-01T10:00:00Z",
+            "2022-01-01T03:00:00Z",
+            "2022-01-01T10:00:00Z",
+            "2022-01-01T01:00:00Z"
+        ]
+    );
+    assert_eq!(
+        xml_texts(&xml, "published"),
+        [
+            "2022-01-01T03:00:00Z",
+            "2022-01-01T02:00:00Z",
+            "2022-01-01T01:00:00Z"
+        ]
+    );
+    assert!(xml_texts(&xml, "content")[0].contains("<strong>post 3</strong>"));
+}
+
+#[actix_web::test]
+async fn json() {
+    let db = setup().await;
+    seed_feed(&db).await;
+
+    let res = call(&db, TestRequest::get().uri("/feed.json")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("application/feed+json"));
+
+    let feed: Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+    assert_eq!(feed["version"], "https://jsonfeed.org/version/1.1");
+    assert_eq!(feed["title"], "My blog");
+    let items = feed["items"].as_array().unwrap();
+    let fields = |name: &str| {
+        items
+            .iter()
+            .map(|item| item[name].as_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(fields("title"), ["Post 03", "Post 02", "Post 01"]);
+    assert_eq!(
+        fields("date_modified"),
+        [
+            "2022-01-01T03:00:00Z",
+            "2022-01-01T10:00:00Z",
+            "2022-01-01T01:00:00Z"
+        ]
+    );
+    assert_eq!(
+        fields("date_published"),
+        [
+            "2022-01-01T03:00:00Z",
+            "2022-01-01T02:00:00Z",
+            "2022-01-01T01:00:00Z"
+        ]
+    );
+    assert_eq!(items[0]["url"], "https://www.example.com/post/post-03");
+    assert_eq!(items[0]["authors"][0]["name"], "jane");
+    assert!(fields("content_html")[0].contains("<strong>post 3</strong>"));
+}
+
+#[actix_web::test]
+async fn empty_atom_and_json() {
+    let db = setup().await;
+
+    let (status, xml) = get(&db, "/feed.atom").await;
+    assert_eq!(status, StatusCode::OK);
+    // Atom needs a date even without entries
+    assert_eq!(xml_texts(&xml, "updated").len(), 1);
+    assert!(!xml.contains("<entry>"));
+
+    let (status, json) = get(&db, "/feed.json").await;
+    assert_eq!(status, StatusCode::OK);
+    let feed: Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(feed["items"], Value::Array(Vec::new()));
+}
+
+#[actix_web::test]
+async fn pages_link_to_every_feed() {
+    let db = setup().await;
+    let author = seed_user(&db, "jane", Role::Author).await;
+    seed_posts(&db, &author, 1).await;
+
+    for uri in ["/", "/post/post-01"] {
+        let (_, html) = get(&db, uri).await;
+        assert_in_order(
+            &html,
+            &[
+                "<link rel=\"alternate\" type=\"application/rss+xml\" title=\"My blog (RSS)\" href=\"/feed.rss\">",
+                "<link rel=\"alternate\" type=\"application/atom+xml\" title=\"My blog (Atom)\" href=\"/feed.atom\">",
+                "<link rel=\"alternate\" type=\"application/feed+json\" title=\"My blog (JSON Feed)\" href=\"/feed.json\">",
+            ],
+        );
+    }
+}
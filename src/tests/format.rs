@@ -0,0 +1,66 @@
+use crate::format::render_preview;
+
+fn preview(body: &str, words: usize) -> (String, bool) {
+    let mut html = String::new();
+    let shortened = render_preview(body, &mut html, words, false);
+    (html, shortened)
+}
+
+#[test]
+fn cut_previews_close_their_tags() {
+    assert_eq!(
+        preview("Some **bold words here**\n\nNext paragraph", 2),
+        ("<p>Some <strong>bold...</strong></p>\n".to_owned(), true)
+    );
+    assert_eq!(
+        preview("> - one\n> - two three four", 3),
+        (
+            "<blockquote>\n<ul>\n<li>one</li>\n<li>two three...</li>\n</ul>\n</blockquote>\n"
+                .to_owned(),
+            true
+        )
+    );
+
+    // Code blocks that are cut are still highlighted
+    let (html, shortened) = preview("```rust\nlet a = 1;\nlet b = 2;\n```", 3);
+    assert!(html.starts_with("<pre class=\"hl-code\"><code class=\"language-rust\">"));
+    assert!(html.ends_with("...</span></span></code></pre>\n"));
+    assert!(!html.contains('2'));
+    assert!(shortened);
+}
+
+#[test]
+fn links_and_code_spans_are_kept_whole() {
+    assert_eq!(
+        preview("Read [the whole link](https://example.com) and more", 2).0,
+        "<p>Read <a href=\"https://example.com\" rel=\"noopener noreferrer\">the whole link</a></p>\n"
+    );
+    assert_eq!(
+        preview("Run `cargo build --release` please", 2).0,
+        "<p>Run <code>cargo build --release</code></p>\n"
+    );
+}
+
+#[test]
+fn posts_that_fit_are_not_shortened() {
+    assert_eq!(
+        preview("Exactly *three words*", 3),
+        ("<p>Exactly <em>three words</em></p>\n".to_owned(), false)
+    );
+    assert_eq!(
+        preview("Before\n\n<!--more-->", 1),
+        ("<p>Before</p>\n".to_owned(), true)
+    );
+}
+
+#[test]
+fn script_links_are_dropped() {
+    assert_eq!(
+        preview(
+            "[click](javascript:alert(1)) ![img](javascript:alert(2))",
+            5
+        )
+        .0,
+        "<p><a rel=\"noopener noreferrer\">click</a> <img alt=\"img\"></p>\n"
+    );
+}
@@ -0,0 +1,146 @@
+use super::*;
+use crate::{config::CONFIG, entities::prelude::Media};
+use actix_web::http::header;
+use image::{ImageFormat, RgbImage};
+use sea_orm::EntityTrait;
+use std::io::Cursor;
+
+const BOUNDARY: &str = "b5-test-boundary";
+
+/// A multipart upload of `bytes` as `file_name`
+fn upload(file_name: &str, bytes: &[u8]) -> TestRequest {
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+        BOUNDARY, file_name
+    )
+    .into_bytes();
+    body.extend_from_slice(bytes);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+    TestRequest::post()
+        .uri("/admin/media")
+        .insert_header((
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        ))
+        .set_payload(body)
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    RgbImage::new(width, height)
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .unwrap();
+    bytes
+}
+
+async fn upload_json(
+    db: &DatabaseConnection,
+    cookie: Cookie<'static>,
+    req: TestRequest,
+) -> serde_json::Value {
+    let res = call(
+        db,
+        req.cookie(cookie)
+            .insert_header((header::ACCEPT, "application/json")),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    serde_json::from_slice(&test::read_body(res).await).unwrap()
+}
+
+#[actix_web::test]
+async fn large_images_get_a_thumbnail() {
+    let db = setup().await;
+    seed_user(&db, "jane", Role::Author).await;
+    let cookie = login(&db, "jane").await;
+
+    let json = upload_json(&db, cookie, upload("My Photo.PNG", &png(1000, 500))).await;
+    let upload = Media::find().one(&db).await.unwrap().unwrap();
+    assert!(upload.file_name.starts_with("my-photo-"));
+    assert!(upload.file_name.ends_with(".png"));
+    assert_eq!(upload.original_name, "My Photo.PNG");
+    assert_eq!(upload.content_type, "image/png");
+    assert_eq!((upload.width, upload.height), (Some(1000), Some(500)));
+
+    let thumb = upload.thumbnail.clone().unwrap();
+    let (width, height) = image::image_dimensions(CONFIG.media.dir.join(&thumb)).unwrap();
+    assert_eq!((width, height), (CONFIG.media.thumbnail_width, 320));
+    assert!(CONFIG.media.dir.join(&upload.file_name).is_file());
+
+    assert_eq!(json["url"], format!("/media/{}", upload.file_name));
+    assert_eq!(
+        json["markdown"],
+        format!(
+            "[![My Photo](/media/{})](/media/{})",
+            thumb, upload.file_name
+        )
+    );
+
+    // The upload is served next to the static files
+    let res = call(
+        &db,
+        TestRequest::get().uri(&format!("/media/{}", upload.file_name)),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "image/png"
+    );
+}
+
+#[actix_web::test]
+async fn small_images_and_other_files() {
+    let db = setup().await;
+    seed_user(&db, "jane", Role::Author).await;
+    let cookie = login(&db, "jane").await;
+
+    let json = upload_json(&db, cookie.clone(), upload("icon.png", &png(16, 16))).await;
+    let markdown = json["markdown"].as_str().unwrap();
+    assert!(markdown.starts_with("![icon](/media/icon-"));
+
+    let json = upload_json(&db, cookie.clone(), upload("Notes [v2].pdf", b"%PDF-1.4")).await;
+    let markdown = json["markdown"].as_str().unwrap();
+    assert!(markdown.starts_with("[Notes v2.pdf](/media/notes-v2-"));
+
+    // Without the editor's script the browser goes to the library, which lists both
+    let res = call(&db, upload("other.png", &png(8, 8)).cookie(cookie.clone())).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(res.headers().get(header::LOCATION).unwrap(), "/admin/media");
+
+    let res = call(&db, TestRequest::get().uri("/admin/media").cookie(cookie)).await;
+    let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert_in_order(&html, &["other.png", "Notes [v2].pdf", "icon.png"]);
+    assert_eq!(Media::find().all(&db).await.unwrap().len(), 3);
+}
+
+#[actix_web::test]
+async fn rejected_uploads() {
+    let db = setup().await;
+    seed_user(&db, "jane", Role::Author).await;
+    seed_user(&db, "commenter", Role::Commenter).await;
+
+    let res = call(&db, upload("photo.png", &png(8, 8))).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let cookie = login(&db, "commenter").await;
+    let res = call(&db, upload("photo.png", &png(8, 8)).cookie(cookie)).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let cookie = login(&db, "jane").await;
+    for (name, bytes) in [
+        ("script.svg", b"<svg></svg>".to_vec()),
+        ("no-extension", b"data".to_vec()),
+        ("fake.png", b"not a png".to_vec()),
+        ("empty.pdf", Vec::new()),
+    ] {
+        let res = call(&db, upload(name, &bytes).cookie(cookie.clone())).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", name);
+    }
+
+    let too_big = vec![0; CONFIG.media.max_size + 1];
+    let res = call(&db, upload("big.pdf", &too_big).cookie(cookie)).await;
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    assert!(Media::find().all(&db).await.unwrap().is_empty());
+}
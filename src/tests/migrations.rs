@@ -0,0 +1,35 @@
+//! Only runs against a Postgres database set in `TEST_POSTGRES_URL`, which it empties:
+//! `TEST_POSTGRES_URL=postgres://... cargo test --features postgres -- --ignored`
+
+use super::*;
+use crate::entities::blog_posts::PostStatus;
+use sea_orm::{ConnectionTrait, Statement};
+
+#[actix_web::test]
+#[ignore]
+async fn migrations_run_on_postgres() {
+    let url = std::env::var("TEST_POSTGRES_URL").expect("TEST_POSTGRES_URL is not set");
+    let db = Database::connect(&url).await.unwrap();
+    Migrator::fresh(&db).await.unwrap();
+
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_post(&db, &author, 1, PostStatus::Published).await;
+    let (status, html) = get(&db, "/post/post-01").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(html.contains("Post 01"));
+    let (_, html) = get(&db, "/search?q=post").await;
+    assert!(html.contains("Post 01"));
+
+    Migrator::down(&db, None).await.unwrap();
+    let tables = db
+        .query_all(Statement::from_string(
+            db.get_database_backend(),
+            "SELECT tablename FROM pg_tables WHERE schemaname = 'public'".to_owned(),
+        ))
+        .await
+        .unwrap()
+        .iter()
+        .map(|row| row.try_get::<String>("", "tablename").unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(tables, ["seaql_migrations"]);
+}
@@ -0,0 +1,155 @@
+//! Runs the app against an in-memory SQLite database with the migrations applied
+
+mod auth;
+mod build;
+mod cache;
+mod cli;
+mod comments;
+mod conditional;
+mod config;
+mod export;
+mod feeds;
+mod format;
+mod media;
+#[cfg(feature = "postgres")]
+mod migrations;
+mod posts;
+mod revisions;
+mod search;
+
+use crate::{
+    auth::Role,
+    entities::{blog_posts, users},
+    routes, session, AppState,
+};
+use actix_web::{
+    body::MessageBody,
+    cookie::{Cookie, Key},
+    dev::ServiceResponse,
+    http::StatusCode,
+    test::{self, TestRequest},
+    web, App,
+};
+use chrono::{Duration, NaiveDate};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{entity::*, Database, DatabaseConnection};
+
+/// Every seeded user logs in with this
+pub const PASSWORD: &str = "password";
+
+pub async fn setup() -> DatabaseConnection {
+    let db = Database::connect("sqlite::memory:")
+        .await
+        .expect("Could not open an in-memory database");
+    Migrator::up(&db, None)
+        .await
+        .expect("Could not run the migrations");
+    db
+}
+
+pub async fn seed_user(db: &DatabaseConnection, username: &str, role: Role) -> users::Model {
+    users::ActiveModel {
+        username: Set(username.to_owned()),
+        // The lowest cost bcrypt allows, to keep the tests fast
+        password: Set(bcrypt::hash(PASSWORD, 4).unwrap()),
+        join_date: Set(date(0)),
+        level: Set(role as users::Level),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap()
+}
+
+/// Seeds "Post 01", "Post 02" and so on. Higher numbers are newer.
+pub async fn seed_post(
+    db: &DatabaseConnection,
+    author: &users::Model,
+    n: u32,
+    status: blog_posts::PostStatus,
+) -> blog_posts::Model {
+    blog_posts::ActiveModel {
+        slug: Set(format!("post-{:02}", n)),
+        title: Set(format!("Post {:02}", n)),
+        body: Set(format!("Body of **post {}**", n)),
+        author_id: Set(author.user_id),
+        post_date: Set(date(n)),
+        status: Set(status),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap()
+}
+
+/// Seeds published posts numbered 1 to `count`
+pub async fn seed_posts(db: &DatabaseConnection, author: &users::Model, count: u32) {
+    for n in 1..=count {
+        seed_post(db, author, n, blog_posts::PostStatus::Published).await;
+    }
+}
+
+/// A date in the past, `hours` after the first seeded one
+pub fn date(hours: u32) -> chrono::NaiveDateTime {
+    NaiveDate::from_ymd_opt(2022, 1, 1)
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .unwrap()
+        + Duration::hours(hours.into())
+}
+
+/// Shared by every app a test builds, so session cookies work across calls
+fn session_key() -> Key {
+    Key::from(&[7; 64])
+}
+
+pub async fn call(db: &DatabaseConnection, req: TestRequest) -> ServiceResponse<impl MessageBody> {
+    let app = test::init_service(
+        App::new()
+            .wrap(session(session_key()))
+            .app_data(web::Data::new(AppState { db: db.clone() }))
+            .configure(routes),
+    )
+    .await;
+    test::call_service(&app, req.to_request()).await
+}
+
+pub async fn get(db: &DatabaseConnection, uri: &str) -> (StatusCode, String) {
+    let res = call(db, TestRequest::get().uri(uri)).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// Logs in as a seeded user and returns the session cookie
+pub async fn login(db: &DatabaseConnection, username: &str) -> Cookie<'static> {
+    let res = call(
+        db,
+        TestRequest::post()
+            .uri("/login")
+            .set_form([("username", username), ("password", PASSWORD)]),
+    )
+    .await;
+    assert_eq!(
+        res.status(),
+        StatusCode::SEE_OTHER,
+        "{} could not log in",
+        username
+    );
+    res.response()
+        .cookies()
+        .next()
+        .expect("Logging in should set a session cookie")
+        .into_owned()
+}
+
+/// Asserts that each of `needles` appears in `haystack`, in order
+#[track_caller]
+pub fn assert_in_order(haystack: &str, needles: &[&str]) {
+    let mut rest = haystack;
+    for needle in needles {
+        match rest.find(needle) {
+            Some(i) => rest = &rest[i + needle.len()..],
+            None => panic!("{:?} is missing or out of order in:\n{}", needle, haystack),
+        }
+    }
+}
@@ -0,0 +1,562 @@
+use super::*;
+use crate::entities::{blog_posts::PostStatus, prelude::*};
+use actix_web::http::header;
+use sea_orm::ConnectionTrait;
+
+#[actix_web::test]
+async fn empty_blog() {
+    let db = setup().await;
+
+    let (status, html) = get(&db, "/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(html.contains("No posts."));
+    assert!(!html.contains("id=\"paginator\""));
+}
+
+#[actix_web::test]
+async fn single_page_has_no_paginator() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 5).await;
+
+    let (_, html) = get(&db, "/posts").await;
+    assert_in_order(
+        &html,
+        &["Post 05", "Post 04", "Post 03", "Post 02", "Post 01"],
+    );
+    assert!(!html.contains("id=\"paginator\""));
+}
+
+#[actix_web::test]
+async fn first_page_links_to_older_posts_only() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 12).await;
+
+    let (status, html) = get(&db, "/posts").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_in_order(
+        &html,
+        &["Post 12", "Post 11", "Post 10", "Post 09", "Post 08"],
+    );
+    assert!(!html.contains("Post 07"));
+    assert!(html.contains("href=\"/posts/before/8\""));
+    assert!(!html.contains("Next</a>"));
+}
+
+#[actix_web::test]
+async fn middle_page_links_both_ways() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 12).await;
+
+    let (_, html) = get(&db, "/posts/before/8").await;
+    assert_in_order(
+        &html,
+        &["Post 07", "Post 06", "Post 05", "Post 04", "Post 03"],
+    );
+    assert!(!html.contains("Post 08"));
+    assert!(!html.contains("Post 02"));
+    assert!(html.contains("href=\"/posts\">&lt; Next"));
+    assert!(html.contains("href=\"/posts/before/3\""));
+}
+
+#[actix_web::test]
+async fn last_page_links_to_newer_posts_only() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 12).await;
+
+    let (_, html) = get(&db, "/posts/before/3").await;
+    assert_in_order(&html, &["Post 02", "Post 01"]);
+    // Links to the page the newer posts are on when paging back from the first one
+    assert!(html.contains("href=\"/posts/before/8\">&lt; Next"));
+    assert!(!html.contains("Previous &gt;"));
+}
+
+#[actix_web::test]
+async fn after_cursor_goes_back_a_page() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 12).await;
+
+    // Newer posts are still shown newest first
+    let (_, html) = get(&db, "/posts?after=2").await;
+    assert_in_order(
+        &html,
+        &["Post 07", "Post 06", "Post 05", "Post 04", "Post 03"],
+    );
+    assert!(html.contains("href=\"/posts\">&lt; Next"));
+    assert!(html.contains("href=\"/posts/before/3\""));
+
+    let (_, html) = get(&db, "/posts?after=7").await;
+    assert_in_order(
+        &html,
+        &["Post 12", "Post 11", "Post 10", "Post 09", "Post 08"],
+    );
+    assert!(!html.contains("Next</a>"));
+}
+
+#[actix_web::test]
+async fn cursor_past_the_end() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 3).await;
+
+    let (status, html) = get(&db, "/posts/before/1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(html.contains("No posts."));
+    assert!(!html.contains("id=\"paginator\""));
+}
+
+#[actix_web::test]
+async fn unpublished_posts_are_not_listed_or_paged_to() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 5).await;
+    seed_post(&db, &author, 6, PostStatus::Draft).await;
+    let mut scheduled: blog_posts::ActiveModel = seed_post(&db, &author, 7, PostStatus::Scheduled)
+        .await
+        .into();
+    scheduled.post_date = Set(chrono::Utc::now().naive_utc() + Duration::days(1));
+    scheduled.update(&db).await.unwrap();
+
+    let (_, html) = get(&db, "/posts").await;
+    assert_in_order(&html, &["Post 05", "Post 01"]);
+    assert!(!html.contains("Post 06"));
+    assert!(!html.contains("Post 07"));
+    assert!(!html.contains("id=\"paginator\""));
+
+    // The draft and scheduled post are newer than the listed ones, but there is no page for them
+    let (_, html) = get(&db, "/posts?before=3").await;
+    assert!(html.contains("href=\"/posts\">&lt; Next"));
+    let (_, html) = get(&db, "/posts?after=5").await;
+    assert!(html.contains("No posts."));
+}
+
+#[actix_web::test]
+async fn post_page() {
+    let db = setup().await;
+    let author = seed_user(&db, "jane", Role::Author).await;
+    seed_posts(&db, &author, 2).await;
+
+    let (status, html) = get(&db, "/post/post-02").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(html.contains("Post 02"));
+    assert!(html.contains("jane"));
+    assert!(html.contains("<strong>post 2</strong>"));
+    assert!(!html.contains("Post 01"));
+}
+
+#[actix_web::test]
+async fn old_post_urls_redirect_to_slugs() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    let post = seed_post(&db, &author, 1, PostStatus::Published).await;
+
+    let res = call(
+        &db,
+        TestRequest::get().uri(&format!("/post/{}", post.post_id)),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(
+        res.headers().get(header::LOCATION).unwrap(),
+        "/post/post-01"
+    );
+}
+
+#[actix_web::test]
+async fn missing_and_draft_posts_are_not_found() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    let draft = seed_post(&db, &author, 1, PostStatus::Draft).await;
+
+    let (status, _) = get(&db, "/post/no-such-post").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = get(&db, "/post/post-01").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = get(&db, &format!("/post/{}", draft.post_id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn code_blocks_are_highlighted() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    let mut post: blog_posts::ActiveModel = seed_post(&db, &author, 1, PostStatus::Published)
+        .await
+        .into();
+    post.body = Set(
+        "Intro\n\n```rust,ignore\nfn main() { println!(\"<b>\"); }\n```\n\n```nolang\n<i>plain</i>\n```"
+            .to_owned(),
+    );
+    post.update(&db).await.unwrap();
+
+    // Previews keep the highlighting of the code they show
+    for uri in ["/post/post-01", "/"] {
+        let (_, html) = get(&db, uri).await;
+        assert_in_order(
+            &html,
+            &[
+                "<pre class=\"hl-code\"><code class=\"language-rust\">",
+                "<span class=\"hl-storage hl-type hl-function hl-rust\">fn</span>",
+                "&lt;b&gt;",
+                "<pre><code class=\"language-nolang\">&lt;i&gt;plain&lt;/i&gt;",
+            ],
+        );
+    }
+}
+
+#[actix_web::test]
+async fn markdown_extensions_and_table_of_contents() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    let mut post: blog_posts::ActiveModel = seed_post(&db, &author, 1, PostStatus::Published)
+        .await
+        .into();
+    post.body = Set([
+        "# Setup",
+        "\"Quoted\" -- text[^note]",
+        "## Setup",
+        "| a | b |\n|---|---|\n| 1 | 2 |",
+        "- [x] done\n- [ ] todo",
+        "## Comments",
+        "[^note]: The footnote",
+    ]
+    .join("\n\n"));
+    post.update(&db).await.unwrap();
+
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert_in_order(
+        &html,
+        &[
+            "<nav class=\"toc\" id=\"toc\">",
+            "<li class=\"toc-depth-0\"><a href=\"#setup\">Setup</a></li>",
+            "<li class=\"toc-depth-1\"><a href=\"#setup-2\">Setup</a></li>",
+            // The page's comment section already has this id
+            "<li class=\"toc-depth-1\"><a href=\"#comments-2\">Comments</a></li>",
+            "<h1 id=\"setup\">Setup</h1>",
+            "“Quoted” – text",
+            "class=\"footnote-reference\"><a href=\"#note\" rel=\"noopener noreferrer\">1</a>",
+            "<h2 id=\"setup-2\">Setup</h2>",
+            "<table>",
+            "<td>1</td>",
+            "<input disabled=\"\" type=\"checkbox\" checked=\"\">",
+            "<h2 id=\"comments-2\">Comments</h2>",
+            "class=\"footnote-definition\" id=\"note\"",
+        ],
+    );
+
+    // Short posts go without
+    let mut post: blog_posts::ActiveModel = seed_post(&db, &author, 2, PostStatus::Published)
+        .await
+        .into();
+    post.body = Set("# Only\n\n## Two headings".to_owned());
+    post.update(&db).await.unwrap();
+    let (_, html) = get(&db, "/post/post-02").await;
+    assert!(html.contains("<h1 id=\"only\">Only</h1>"));
+    assert!(!html.contains("id=\"toc\""));
+}
+
+#[actix_web::test]
+async fn html_is_sanitized_for_trusted_authors_only() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    let moderator = seed_user(&db, "moderator", Role::Mod).await;
+    let body = [
+        "# Embeds",
+        "<details><summary>More</summary>\n\nHidden *text*\n\n</details>",
+        "<iframe src=\"https://www.youtube-nocookie.com/embed/abc\"></iframe>",
+        "<iframe src=\"https://evil.example.com/\"></iframe>",
+        "<script>alert(1)</script><img src=\"x.png\" onerror=\"alert(1)\">",
+        "[click](javascript:alert(1))",
+        "```rust\nlet x = 1;\n```",
+    ]
+    .join("\n\n");
+    for (n, user) in [(1, &author), (2, &moderator)] {
+        let mut post: blog_posts::ActiveModel =
+            seed_post(&db, user, n, PostStatus::Published).await.into();
+        post.body = Set(body.clone());
+        post.update(&db).await.unwrap();
+    }
+
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains("<h1 id=\"embeds\">Embeds</h1>"));
+    assert!(html.contains("&lt;details&gt;&lt;summary&gt;More"));
+    assert!(html.contains("&lt;script&gt;"));
+    assert!(html.contains("<a rel=\"noopener noreferrer\">click</a>"));
+
+    // The home page previews both posts
+    for uri in ["/post/post-02", "/"] {
+        let (_, html) = get(&db, uri).await;
+        assert_in_order(
+            &html,
+            &[
+                "Embeds</h1>",
+                "<details><summary>More</summary>",
+                "Hidden <em>text</em>",
+                "</details>",
+                "<iframe src=\"https://www.youtube-nocookie.com/embed/abc\"></iframe>",
+                "<iframe></iframe>",
+                "<img src=\"x.png\">",
+                "<span class=\"hl-storage hl-type hl-rust\">let</span>",
+            ],
+        );
+        assert!(!html.contains("<script>alert"));
+        assert!(!html.contains("<img src=\"x.png\" onerror"));
+        assert!(!html.contains("javascript:"));
+    }
+}
+
+#[actix_web::test]
+async fn previews_end_at_the_marker_or_the_post_length() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    let mut marked: blog_posts::ActiveModel = seed_post(&db, &author, 1, PostStatus::Published)
+        .await
+        .into();
+    marked.body = Set("First *part*\n\n<!--more-->\n\nSecond part".to_owned());
+    marked.update(&db).await.unwrap();
+    let mut short: blog_posts::ActiveModel = seed_post(&db, &author, 2, PostStatus::Published)
+        .await
+        .into();
+    short.body = Set("one two three four five".to_owned());
+    short.preview_size = Set(Some(3));
+    short.update(&db).await.unwrap();
+    seed_post(&db, &author, 3, PostStatus::Published).await;
+
+    let (_, html) = get(&db, "/").await;
+    assert_in_order(
+        &html,
+        &[
+            "Body of <strong>post 3</strong>",
+            "<p>one two three...</p>",
+            "href=\"/post/post-02\">Read more</a>",
+            "<p>First <em>part</em></p>",
+            "href=\"/post/post-01\">Read more</a>",
+        ],
+    );
+    assert!(!html.contains("four"));
+    assert!(!html.contains("Second part"));
+    assert!(!html.contains("href=\"/post/post-03\">Read more"));
+
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains("Second part"));
+    assert!(!html.contains("more--"));
+}
+
+#[actix_web::test]
+async fn failed_edits_keep_the_post_and_its_tags() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    let post = seed_post(&db, &author, 1, PostStatus::Published).await;
+    crate::tags::set_post_tags(&db, post.post_id, "Rust")
+        .await
+        .unwrap();
+    // Make the tags fail to save after the old ones are gone
+    db.execute(sea_orm::Statement::from_string(
+        db.get_database_backend(),
+        "CREATE TRIGGER no_tags BEFORE INSERT ON post_tags BEGIN SELECT RAISE(ABORT, 'no tags'); END"
+            .to_owned(),
+    ))
+    .await
+    .unwrap();
+
+    let res = call(
+        &db,
+        TestRequest::post()
+            .uri(&format!("/admin/posts/{}/edit", post.post_id))
+            .cookie(login(&db, "author").await)
+            .set_form([
+                ("title", "Post 01"),
+                ("description", ""),
+                ("body", "Changed"),
+                ("tags", "Web"),
+                ("publish", "true"),
+            ]),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains("Body of <strong>post 1</strong>"));
+    assert!(html.contains("href=\"/tag/rust\""));
+}
+
+#[actix_web::test]
+async fn renamed_posts_redirect_from_their_old_slugs() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    let post = seed_post(&db, &author, 1, PostStatus::Published).await;
+
+    for slug in ["renamed", "renamed-again"] {
+        let res = call(
+            &db,
+            TestRequest::post()
+                .uri(&format!("/admin/posts/{}/edit", post.post_id))
+                .cookie(login(&db, "author").await)
+                .set_form([
+                    ("title", "Post 01"),
+                    ("description", ""),
+                    ("body", "Body"),
+                    ("slug", slug),
+                    ("publish", "true"),
+                ]),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    }
+
+    for old in ["/post/post-01", "/post/renamed"] {
+        let res = call(&db, TestRequest::get().uri(old)).await;
+        assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            res.headers().get(header::LOCATION).unwrap(),
+            "/post/renamed-again"
+        );
+    }
+
+    // A new post can take an old slug over
+    let (status, _) = get(&db, "/post/renamed-again").await;
+    assert_eq!(status, StatusCode::OK);
+    seed_post(&db, &author, 1, PostStatus::Published).await;
+    let (status, html) = get(&db, "/post/post-01").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(html.contains("Body of <strong>post 1</strong>"));
+}
+
+#[actix_web::test]
+async fn slug_clashes_are_form_errors() {
+    let db = setup().await;
+    seed_user(&db, "author", Role::Author).await;
+    // Stands in for another save taking the slug after it was checked
+    db.execute(sea_orm::Statement::from_string(
+        db.get_database_backend(),
+        "CREATE TRIGGER slug_taken BEFORE INSERT ON blog_posts \
+         BEGIN SELECT RAISE(ABORT, 'UNIQUE constraint failed: blog_posts.slug'); END"
+            .to_owned(),
+    ))
+    .await
+    .unwrap();
+
+    let res = call(
+        &db,
+        TestRequest::post()
+            .uri("/admin/posts/new")
+            .cookie(login(&db, "author").await)
+            .set_form([
+                ("title", "Hello"),
+                ("description", ""),
+                ("body", "Unsaved words"),
+                ("publish", "true"),
+            ]),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(html.contains("Another post just took that slug"));
+    assert!(html.contains("Unsaved words"));
+}
+
+#[actix_web::test]
+async fn scheduled_posts_show_as_published_once_live() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    // Its date passed without it being saved again
+    let post = seed_post(&db, &author, 1, PostStatus::Scheduled).await;
+
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri("/admin/posts")
+            .cookie(login(&db, "author").await),
+    )
+    .await;
+    let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(html.contains("<td>Published</td>"));
+    assert!(!html.contains("Scheduled"));
+
+    // Saving with the date left blank keeps the date it went live on
+    let res = call(
+        &db,
+        TestRequest::post()
+            .uri(&format!("/admin/posts/{}/edit", post.post_id))
+            .cookie(login(&db, "author").await)
+            .set_form([
+                ("title", "Post 01"),
+                ("description", ""),
+                ("body", "Changed"),
+                ("post_date", ""),
+                ("publish", "true"),
+            ]),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    let saved = BlogPosts::find_by_id(post.post_id)
+        .one(&db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(saved.post_date, post.post_date);
+    assert_eq!(saved.status, PostStatus::Published);
+}
+
+#[actix_web::test]
+async fn saving_keeps_the_stored_date() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    let mut post: blog_posts::ActiveModel = seed_post(&db, &author, 1, PostStatus::Published)
+        .await
+        .into();
+    post.post_date = Set(date(1) + Duration::milliseconds(42_500));
+    let post = post.update(&db).await.unwrap();
+    let cookie = login(&db, "author").await;
+
+    // Save with the date the editor was filled in with
+    let res = call(
+        &db,
+        TestRequest::get()
+            .uri(&format!("/admin/posts/{}/edit", post.post_id))
+            .cookie(cookie.clone()),
+    )
+    .await;
+    let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    let value = html
+        .split("name=\"post_date\"")
+        .nth(1)
+        .and_then(|input| input.split("value=\"").nth(1))
+        .and_then(|value| value.split('"').next())
+        .unwrap()
+        .to_owned();
+    assert_eq!(value, "2022-01-01T01:00:42");
+
+    for (post_date, saved) in [
+        (value.as_str(), post.post_date),
+        // Browsers leave off zero seconds
+        ("2022-01-01T02:00", date(2)),
+        ("2022-01-01T03:00:15", date(3) + Duration::seconds(15)),
+    ] {
+        let res = call(
+            &db,
+            TestRequest::post()
+                .uri(&format!("/admin/posts/{}/edit", post.post_id))
+                .cookie(cookie.clone())
+                .set_form([
+                    ("title", "Post 01"),
+                    ("description", ""),
+                    ("body", "Changed"),
+                    ("post_date", post_date),
+                    ("publish", "true"),
+                ]),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        let post = BlogPosts::find_by_id(post.post_id)
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(post.post_date, saved, "{}", post_date);
+    }
+}
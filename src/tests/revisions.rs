@@ -0,0 +1,106 @@
+use super::*;
+use crate::entities::{blog_posts::PostStatus, prelude::*};
+use actix_web::http::header;
+use sea_orm::{ConnectionTrait, EntityTrait, PaginatorTrait};
+
+/// Saves the post with a new body, which also saves a revision
+async fn edit(db: &DatabaseConnection, cookie: Cookie<'static>, post_id: u32, body: &str) {
+    let res = call(
+        db,
+        TestRequest::post()
+            .uri(&format!("/admin/posts/{}/edit", post_id))
+            .cookie(cookie)
+            .set_form([
+                ("title", "Post 01"),
+                ("description", ""),
+                ("body", body),
+                ("publish", "true"),
+            ]),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+}
+
+fn restore(post_id: u32, revision_id: u32, cookie: Cookie<'static>) -> TestRequest {
+    TestRequest::post()
+        .uri(&format!(
+            "/admin/posts/{}/revisions/{}/restore",
+            post_id, revision_id
+        ))
+        .cookie(cookie)
+}
+
+#[actix_web::test]
+async fn restoring_saves_a_new_revision() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    let post = seed_post(&db, &jane, 1, PostStatus::Published).await;
+    let cookie = login(&db, "jane").await;
+    edit(&db, cookie.clone(), post.post_id, "First draft").await;
+    edit(&db, cookie.clone(), post.post_id, "Second draft").await;
+
+    let res = call(&db, restore(post.post_id, 1, cookie.clone())).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(
+        res.headers().get(header::LOCATION).unwrap(),
+        "/admin/posts/1/revisions"
+    );
+
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains("First draft"));
+    let revisions = PostRevisions::find().all(&db).await.unwrap();
+    let bodies: Vec<_> = revisions.iter().map(|rev| rev.body.as_str()).collect();
+    assert_eq!(bodies, ["First draft", "Second draft", "First draft"]);
+
+    // The restore can be undone like any other change
+    let res = call(&db, restore(post.post_id, 2, cookie.clone())).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains("Second draft"));
+
+    // Revisions only restore onto their own post
+    let other = seed_post(&db, &jane, 2, PostStatus::Published).await;
+    let res = call(&db, restore(other.post_id, 1, cookie)).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn only_editors_of_the_post_can_restore() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    seed_user(&db, "john", Role::Author).await;
+    let post = seed_post(&db, &jane, 1, PostStatus::Published).await;
+    edit(&db, login(&db, "jane").await, post.post_id, "First draft").await;
+    edit(&db, login(&db, "jane").await, post.post_id, "Second draft").await;
+
+    let res = call(&db, restore(post.post_id, 1, login(&db, "john").await)).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains("Second draft"));
+    assert_eq!(PostRevisions::find().count(&db).await.unwrap(), 2);
+}
+
+#[actix_web::test]
+async fn failed_restores_keep_the_post() {
+    let db = setup().await;
+    let jane = seed_user(&db, "jane", Role::Author).await;
+    let post = seed_post(&db, &jane, 1, PostStatus::Published).await;
+    let cookie = login(&db, "jane").await;
+    edit(&db, cookie.clone(), post.post_id, "First draft").await;
+    edit(&db, cookie.clone(), post.post_id, "Second draft").await;
+    // Make the new revision fail to save after the post is updated
+    db.execute(sea_orm::Statement::from_string(
+        db.get_database_backend(),
+        "CREATE TRIGGER no_revisions BEFORE INSERT ON post_revisions BEGIN SELECT RAISE(ABORT, 'no revisions'); END"
+            .to_owned(),
+    ))
+    .await
+    .unwrap();
+
+    let res = call(&db, restore(post.post_id, 1, cookie)).await;
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (_, html) = get(&db, "/post/post-01").await;
+    assert!(html.contains("Second draft"));
+}
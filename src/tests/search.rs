@@ -0,0 +1,63 @@
+use super::*;
+
+#[actix_web::test]
+async fn search_pages_are_bounded() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    seed_posts(&db, &author, 6).await;
+
+    let (status, html) = get(&db, "/search?q=post").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(html.contains("/search?q=post&amp;page=2"));
+
+    let (status, _) = get(&db, "/search?q=post&page=1000").await;
+    assert_eq!(status, StatusCode::OK);
+
+    for page in ["1001", "18446744073709551615", "18446744073709551616"] {
+        let (status, _) = get(&db, &format!("/search?q=post&page={}", page)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "page {}", page);
+    }
+}
+
+#[actix_web::test]
+async fn results_match_every_term_and_skip_drafts() {
+    let db = setup().await;
+    let author = seed_user(&db, "author", Role::Author).await;
+    for (n, status, body) in [
+        (1, blog_posts::PostStatus::Published, "Rust is 100% safe"),
+        (2, blog_posts::PostStatus::Published, "Rust uses snake_case"),
+        (
+            3,
+            blog_posts::PostStatus::Published,
+            "Rust uses snakeXcase and 1000 ways",
+        ),
+        (4, blog_posts::PostStatus::Draft, "Rust drafts are hidden"),
+        (5, blog_posts::PostStatus::Published, "Go is fine too"),
+    ] {
+        let mut post: blog_posts::ActiveModel = seed_post(&db, &author, n, status).await.into();
+        post.body = Set(body.to_owned());
+        post.update(&db).await.unwrap();
+    }
+
+    let found = |html: &str| {
+        (1..=5)
+            .filter(|n| html.contains(&format!("/post/post-{:02}", n)))
+            .collect::<Vec<_>>()
+    };
+    for (terms, expected) in [
+        ("rust", vec![1, 2, 3]),
+        ("rust uses", vec![2, 3]),
+        ("hidden", vec![]),
+        ("100%", vec![1]),
+        ("snake_case", vec![2]),
+        ("%", vec![1]),
+        ("_", vec![2]),
+    ] {
+        let query = terms.replace('%', "%25").replace(' ', "+");
+        let (status, html) = get(&db, &format!("/search?q={}", query)).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut found = found(&html);
+        found.sort_unstable();
+        assert_eq!(found, expected, "{}", terms);
+    }
+}
@@ -0,0 +1,30 @@
+// Uploads files without leaving the editor and inserts their markdown at the cursor.
+// Without this the upload form goes to the media library instead.
+const uploadForm = document.getElementById("upload-form");
+const body = document.getElementById("body");
+const uploadStatus = document.getElementById("upload-status");
+
+uploadForm.addEventListener("submit", async (event) => {
+    event.preventDefault();
+    uploadStatus.textContent = "Uploading...";
+
+    try {
+        const res = await fetch(uploadForm.action, {
+            method: "POST",
+            headers: { Accept: "application/json" },
+            body: new FormData(uploadForm),
+        });
+        if (!res.ok) {
+            uploadStatus.textContent = await res.text();
+            return;
+        }
+
+        const { markdown } = await res.json();
+        body.setRangeText(markdown, body.selectionStart, body.selectionEnd, "end");
+        body.focus();
+        document.getElementById("upload").value = "";
+        uploadStatus.textContent = "";
+    } catch (e) {
+        uploadStatus.textContent = "The upload failed";
+    }
+});